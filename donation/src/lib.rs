@@ -4,45 +4,678 @@ use core::fmt::Debug;
 
 type DonationLocation = String;
 
+/// Maximum length of a location name in bytes.
+const MAX_LOCATION_LENGTH: usize = 32;
+
+/// Maximum length of a donation tag in bytes.
+const MAX_TAG_LENGTH: usize = 32;
+
+/// Maximum number of entries returned by `view_top_tags`.
+const MAX_TOP_TAGS: u32 = 10;
+
+/// Number of donations kept for `view_recent_donations`.
+const MAX_RECENT_DONATIONS: u32 = 20;
+
+/// Maximum number of entries returned by `view_leaderboard`.
+const MAX_LEADERBOARD: u32 = 10;
+
+/// Maximum number of checkpoints that can be taken.
+const MAX_CHECKPOINTS: usize = 50;
+
 /// Your smart contract state.
-#[derive(Serialize, SchemaType, Clone)]
-pub struct State {
+#[derive(Serial, DeserialWithState, StateClone)]
+#[concordium(state_parameter = "S")]
+pub struct State<S> {
     number_of_donors: u32,
     state_of_donation: StateOfDonation,
     donation_locations: Vec<DonationLocation>,
     end_time: Timestamp,
+    /// Amount the campaign aims to raise.
+    goal: Amount,
+    /// Whether the goal can no longer be changed.
+    goal_locked: bool,
+    /// Whether donations are trimmed to the goal, refunding the excess.
+    trim_to_goal: bool,
+    /// Milestones as fractions of the goal in basis points, ascending.
+    milestones_bps: Vec<u32>,
+    /// Time at which the contract was initialized.
+    start_time: Timestamp,
+    /// Sum of all donations received.
+    total_donated: Amount,
+    /// Sum of everything transferred out by `close` and `withdraw`.
+    total_paid_out: Amount,
+    /// Sum of the donations received for each location.
+    location_totals: StateMap<DonationLocation, Amount, S>,
+    /// Targets of the locations that have their own goal.
+    location_goals: StateMap<DonationLocation, Amount, S>,
+    /// Every donor that has donated to each location.
+    location_donors: StateSet<(DonationLocation, AccountAddress), S>,
+    /// Number of distinct donors of each location that has any.
+    location_donor_counts: StateMap<DonationLocation, u32, S>,
+    /// Cumulative amount donated by each donor.
+    donations: StateMap<AccountAddress, Amount, S>,
+    /// Cumulative total from which a donor is recognised as a major donor.
+    recognition_threshold: Option<Amount>,
+    /// Donors for whom a `MajorDonor` event has already been logged.
+    major_donors: StateSet<AccountAddress, S>,
+    /// Contract queried for the CCD price.
+    oracle: Option<ContractAddress>,
+    /// Last price returned by the oracle, in fiat cents per CCD.
+    fiat_rate: Option<u64>,
+    /// Minimum donation in fiat cents, enforced once a rate is known.
+    min_donation_fiat: Option<u64>,
+    /// Whether `donate` accepts donations. Disabled for view-only campaigns.
+    donations_enabled: bool,
+    /// Deferred donations waiting for their activation time, by id.
+    pending: StateMap<u64, PendingDonation, S>,
+    /// Id given to the next deferred donation.
+    next_pending_id: u64,
+    /// CCD held for deferred donations, not yet counted anywhere else.
+    pending_total: Amount,
+    /// Ids of the deferred donations cancelled by their donors.
+    cancelled_pending: StateSet<u64, S>,
+    /// Hard cap on the total that can be donated.
+    max_total: Option<Amount>,
+    /// Whether reaching `max_total` closes the campaign.
+    auto_close_on_cap: bool,
+    /// Whether donations to a closed campaign are queued until it reopens.
+    queue_while_closed: bool,
+    /// Sum of `amount * weight` over all counted donations, see `view_twa`.
+    weighted_amount_sum: u128,
+    /// Sum of the weights of all counted donations, see `view_twa`.
+    weight_sum: u128,
+    /// Number of locations that must have received funds before closing.
+    min_funded_locations: u32,
+    /// Cap on the total a single donor can donate.
+    max_per_donor: Option<Amount>,
+    /// Donors not subject to `max_per_donor`.
+    exempt_from_donor_cap: StateSet<AccountAddress, S>,
+    /// Sum of the donations received under each tag.
+    tag_totals: StateMap<String, Amount, S>,
+    /// The last `MAX_RECENT_DONATIONS` counted donations, oldest first, with
+    /// the time they were counted.
+    recent_donations: Vec<(Donation, Timestamp)>,
+    /// `total_donated` at the times the owner took a checkpoint, oldest first.
+    checkpoints: Vec<(Timestamp, Amount)>,
+    /// Every address that has sent a donation.
+    donor_senders: StateSet<Address, S>,
+    /// Number of distinct accounts that have sent a donation.
+    account_donors: u32,
+    /// Number of distinct contracts that have sent a donation.
+    contract_donors: u32,
+    /// Donors who made an anonymous donation, hidden in the leaderboard.
+    anonymous_donors: StateSet<AccountAddress, S>,
+    /// Donors who hid the amount of a donation, shown without their total.
+    hidden_amount_donors: StateSet<AccountAddress, S>,
+    /// Time from which the campaign is paused and `donate` is rejected.
+    scheduled_pause_at: Option<Timestamp>,
+    /// How long after `end_time` donations are still accepted.
+    grace_period: Duration,
+    /// Whether donations in the grace period go to `late_total` instead of
+    /// `total_donated`.
+    grace_donations_flagged: bool,
+    /// Sum of the donations received in the grace period while flagged,
+    /// not counted towards the goal.
+    late_total: Amount,
+    /// Accounts that can approve `withdraw` and `close`, including the
+    /// contract owner.
+    owners: StateSet<AccountAddress, S>,
+    /// Number of distinct owners that must approve `withdraw` or `close`
+    /// before it is executed.
+    approval_threshold: u32,
+    /// The action currently collecting approvals.
+    proposal: Option<Proposal>,
+    /// Donations above this amount extend `end_time`.
+    extension_threshold: Option<Amount>,
+    /// How much a large donation extends `end_time` by.
+    extension_increment: Duration,
+    /// Cap on the sum of all extensions.
+    max_extension: Duration,
+    /// Sum of the extensions granted so far.
+    total_extension: Duration,
+    /// Number of calls to mutable entrypoints, see `view_activity`.
+    total_calls: u64,
+    /// Fraction of each counted donation diverted into `match_pool`, in
+    /// basis points.
+    self_match_bps: u32,
+    /// CCD set aside to match later donations one to one.
+    match_pool: Amount,
+    /// Sum of the match pool paid out to the owner when closing.
+    released_pool: Amount,
+    /// Progress of `rebate_surplus`, once started.
+    rebate: Option<RebateProgress>,
+    /// CCD the contract was funded with at init, not a donation.
+    seed_balance: Amount,
+    /// Whether `donate` matches locations ignoring ASCII case when there is
+    /// no exact match.
+    case_insensitive_locations: bool,
+    /// Remainders below this amount left in a closed campaign are dust and
+    /// swept to the owner.
+    dust_threshold: Amount,
+    /// Fraction of each refund kept by the campaign, in basis points.
+    refund_fee_bps: u32,
+    /// Whether the contract has been frozen for good by `finalize_forever`.
+    finalized: bool,
+}
+
+impl<S: HasStateApi> State<S> {
+    fn new(
+        param: InitParameter,
+        owner: AccountAddress,
+        start_time: Timestamp,
+        state_builder: &mut StateBuilder<S>,
+    ) -> Self {
+        let mut milestones_bps = param.milestones_bps;
+        milestones_bps.sort_unstable();
+        milestones_bps.dedup();
+        let mut location_totals = state_builder.new_map();
+        for location in param.donation_locations.iter() {
+            location_totals.insert(location.clone(), Amount::zero());
+        }
+        let mut location_goals = state_builder.new_map();
+        for (location, goal) in param.location_goals {
+            location_goals.insert(location, goal);
+        }
+        let mut owners = state_builder.new_set();
+        owners.insert(owner);
+        for co_owner in param.co_owners {
+            owners.insert(co_owner);
+        }
+        State {
+            number_of_donors: 0,
+            state_of_donation: StateOfDonation::Open,
+            donation_locations: param.donation_locations,
+            end_time: param.end_time,
+            goal: param.goal,
+            goal_locked: false,
+            trim_to_goal: param.trim_to_goal,
+            milestones_bps,
+            start_time,
+            total_donated: Amount::zero(),
+            total_paid_out: Amount::zero(),
+            location_totals,
+            location_goals,
+            location_donors: state_builder.new_set(),
+            location_donor_counts: state_builder.new_map(),
+            donations: state_builder.new_map(),
+            recognition_threshold: param.recognition_threshold,
+            major_donors: state_builder.new_set(),
+            oracle: param.oracle,
+            fiat_rate: None,
+            min_donation_fiat: param.min_donation_fiat,
+            donations_enabled: param.donations_enabled,
+            pending: state_builder.new_map(),
+            next_pending_id: 0,
+            pending_total: Amount::zero(),
+            cancelled_pending: state_builder.new_set(),
+            max_total: param.max_total,
+            auto_close_on_cap: param.auto_close_on_cap,
+            queue_while_closed: param.queue_while_closed,
+            weighted_amount_sum: 0,
+            weight_sum: 0,
+            min_funded_locations: param.min_funded_locations,
+            max_per_donor: param.max_per_donor,
+            exempt_from_donor_cap: state_builder.new_set(),
+            tag_totals: state_builder.new_map(),
+            recent_donations: Vec::new(),
+            checkpoints: Vec::new(),
+            donor_senders: state_builder.new_set(),
+            account_donors: 0,
+            contract_donors: 0,
+            anonymous_donors: state_builder.new_set(),
+            hidden_amount_donors: state_builder.new_set(),
+            scheduled_pause_at: param.scheduled_pause_at,
+            grace_period: param.grace_period,
+            grace_donations_flagged: param.grace_donations_flagged,
+            late_total: Amount::zero(),
+            owners,
+            approval_threshold: param.approval_threshold,
+            proposal: None,
+            extension_threshold: param.extension_threshold,
+            extension_increment: param.extension_increment,
+            max_extension: param.max_extension,
+            total_extension: Duration::from_millis(0),
+            total_calls: 0,
+            self_match_bps: param.self_match_bps,
+            match_pool: Amount::zero(),
+            released_pool: Amount::zero(),
+            rebate: None,
+            seed_balance: param.seed_amount,
+            case_insensitive_locations: param.case_insensitive_locations,
+            dust_threshold: param.dust_threshold,
+            refund_fee_bps: param.refund_fee_bps,
+            finalized: false,
+        }
+    }
+
+    /// Counts a donation towards the totals of the donor and the location.
+    fn record_donation(
+        &mut self,
+        donation: Donation,
+        now: Timestamp,
+        logger: &mut impl HasLogger,
+    ) -> Result<(), Error> {
+        let donor = donation.donor;
+
+        // matching the donation from the pool, then diverting a fraction of
+        // it into the pool
+        let matched = self.match_pool.min(donation.amount);
+        let diverted = Amount::from_micro_ccd(
+            (u128::from(donation.amount.micro_ccd) * u128::from(self.self_match_bps) / 10000) as u64,
+        );
+        self.match_pool = self.match_pool - matched + diverted;
+        let amount = donation.amount - diverted + matched;
+
+        if self.grace_donations_flagged && now > self.end_time {
+            self.late_total += amount;
+        } else {
+            self.total_donated += amount;
+        }
+        if let Some(tag) = &donation.tag {
+            *self.tag_totals.entry(tag.clone()).or_insert(Amount::zero()) += amount;
+        }
+        let weight = u128::from(self.end_time.timestamp_millis().saturating_sub(now.timestamp_millis()));
+        self.weighted_amount_sum += u128::from(amount.micro_ccd) * weight;
+        self.weight_sum += weight;
+        if let Some(mut location_total) = self.location_totals.get_mut(&donation.location) {
+            *location_total += amount;
+        }
+        if self.location_donors.insert((donation.location.clone(), donor)) {
+            *self.location_donor_counts.entry(donation.location.clone()).or_insert(0) += 1;
+        }
+        if self.donations.get(&donor).is_none() {
+            self.number_of_donors += 1;
+        }
+        if donation.anonymous {
+            self.anonymous_donors.insert(donor);
+        }
+        if donation.hide_amount {
+            self.hidden_amount_donors.insert(donor);
+        }
+        let total = {
+            let mut donated = self.donations.entry(donor).or_insert(Amount::zero());
+            *donated += donation.amount;
+            *donated
+        };
+        logger.log(&DonationEvent::NewDonation {
+            donor,
+            location: donation.location.clone(),
+            amount: donation.amount,
+        })?;
+
+        // extending the deadline for large donations, once they count
+        self.extend_for(donation.amount);
+
+        // keeping the feed of recent donations bounded
+        if self.recent_donations.len() >= MAX_RECENT_DONATIONS as usize {
+            self.recent_donations.remove(0);
+        }
+        self.recent_donations.push((donation, now));
+
+        // recognising donors whose total first reaches the threshold
+        if let Some(threshold) = self.recognition_threshold {
+            if total >= threshold && self.major_donors.insert(donor) {
+                logger.log(&DonationEvent::MajorDonor {
+                    donor,
+                    total,
+                })?;
+            }
+        }
+
+        // closing as soon as the hard cap is reached
+        if let Some(max_total) = self.max_total {
+            if self.auto_close_on_cap
+                && self.received_with_pool() >= max_total
+                && self.state_of_donation == StateOfDonation::Open
+            {
+                self.state_of_donation = StateOfDonation::Closed;
+                logger.log(&DonationEvent::Closed)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Holds a donation without counting it, until `activate_at` or, if that
+    /// is `None`, until the campaign is reopened.
+    fn hold_donation(&mut self, donation: Donation, activate_at: Option<Timestamp>) {
+        let id = self.next_pending_id;
+        self.next_pending_id += 1;
+        self.pending_total += donation.amount;
+        self.pending.insert(id, PendingDonation {
+            donation,
+            activate_at,
+        });
+    }
+
+    /// Counts the held donations selected by `activate`.
+    fn activate_held(
+        &mut self,
+        activate: impl Fn(&PendingDonation) -> bool,
+        now: Timestamp,
+        logger: &mut impl HasLogger,
+    ) -> Result<(), Error> {
+        // a running rebate walks the donors by position, which a new donor
+        // would shift; `donate` cannot count while it runs, as it is closed
+        ensure!(!self.rebate_in_progress(), Error::RebateInProgress);
+
+        let mut activated: Vec<(u64, PendingDonation)> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| activate(pending))
+            .map(|(id, pending)| (*id, pending.clone()))
+            .collect();
+        // counting them in the order they were made
+        activated.sort_by_key(|(id, _)| *id);
+
+        for (id, pending) in activated {
+            self.pending.remove(&id);
+            self.pending_total -= pending.donation.amount;
+            self.record_donation(pending.donation, now, logger)?;
+        }
+        Ok(())
+    }
+
+    /// The minimum donation in CCD, from `min_donation_fiat` converted at the
+    /// stored rate and rounded up. `None` while no (nonzero) rate is known.
+    fn min_donation_ccd(&self) -> Option<Amount> {
+        let min_fiat = u128::from(self.min_donation_fiat?);
+        let rate = u128::from(self.fiat_rate.filter(|rate| *rate > 0)?);
+        let micro_ccd = (min_fiat * 1_000_000).div_ceil(rate);
+        Some(Amount::from_micro_ccd(micro_ccd.min(u128::from(u64::MAX)) as u64))
+    }
+
+    /// All donors with their totals, largest first and ties ordered by
+    /// address.
+    fn ranked_donors(&self) -> Vec<(AccountAddress, Amount)> {
+        let mut donors: Vec<(AccountAddress, Amount)> =
+            self.donations.iter().map(|(donor, total)| (*donor, *total)).collect();
+        donors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        donors
+    }
+
+    /// The location named `location`, matched exactly or, if
+    /// `case_insensitive_locations` is set and there is no exact match,
+    /// ignoring ASCII case.
+    fn resolve_location(&self, location: &str) -> Option<DonationLocation> {
+        let locations = &self.donation_locations;
+        let exact = locations.iter().find(|candidate| candidate.as_str() == location);
+        let resolved = exact.or_else(|| {
+            if self.case_insensitive_locations {
+                locations.iter().find(|candidate| candidate.eq_ignore_ascii_case(location))
+            } else {
+                None
+            }
+        });
+        resolved.cloned()
+    }
+
+    /// The total and goal of a location.
+    fn location_view(&self, location: &DonationLocation) -> LocationView {
+        let total = self.location_totals.get(location).map_or(Amount::zero(), |total| *total);
+        let goal = self.location_goals.get(location).map(|goal| *goal);
+        LocationView {
+            location: location.clone(),
+            total,
+            donors: self.location_donor_counts.get(location).map_or(0, |donors| *donors),
+            goal,
+            goal_reached: goal.is_some_and(|goal| total >= goal),
+        }
+    }
+
+    /// Last time at which donations are accepted, including the grace period.
+    fn deadline(&self) -> Timestamp {
+        self.end_time
+            .checked_add(self.grace_period)
+            .unwrap_or_else(|| Timestamp::from_timestamp_millis(u64::MAX))
+    }
+
+    /// Everything received and counted, including late donations.
+    fn total_received(&self) -> Amount {
+        self.total_donated + self.late_total
+    }
+
+    /// `total_donated` plus the match pool, which was donated too and goes
+    /// to the owner on close, as measured against the goal.
+    fn raised(&self) -> Amount {
+        self.total_donated + self.match_pool
+    }
+
+    /// Everything received, including late donations and the match pool, as
+    /// measured against `max_total`.
+    fn received_with_pool(&self) -> Amount {
+        self.total_received() + self.match_pool
+    }
+
+    /// The account of `sender` if it is one of the owners.
+    fn owner_account(&self, sender: Address) -> Option<AccountAddress> {
+        match sender {
+            Address::Account(account) if self.owners.contains(&account) => Some(account),
+            _ => None,
+        }
+    }
+
+    /// Records the approval of `owner` for `action`, replacing any proposal
+    /// for a different action. Returns whether the action has reached the
+    /// threshold, in which case the proposal is cleared and the caller must
+    /// execute it.
+    fn approve(&mut self, owner: AccountAddress, action: OwnerAction) -> bool {
+        let mut proposal = match self.proposal.take() {
+            Some(proposal) if proposal.action == action => proposal,
+            _ => Proposal {
+                action,
+                approvals: Vec::new(),
+            },
+        };
+        if !proposal.approvals.contains(&owner) {
+            proposal.approvals.push(owner);
+        }
+        if proposal.approvals.len() >= self.approval_threshold as usize {
+            true
+        } else {
+            self.proposal = Some(proposal);
+            false
+        }
+    }
+
+    /// Extends `end_time` by `extension_increment` if `amount` is above the
+    /// extension threshold, as far as `max_extension` allows.
+    fn extend_for(&mut self, amount: Amount) {
+        if self.extension_threshold.is_none_or(|threshold| amount <= threshold) {
+            return;
+        }
+        let left = self.max_extension.millis().saturating_sub(self.total_extension.millis());
+        let extension = Duration::from_millis(self.extension_increment.millis().min(left));
+        if let Some(end_time) = self.end_time.checked_add(extension) {
+            self.end_time = end_time;
+            self.total_extension = Duration::from_millis(self.total_extension.millis() + extension.millis());
+        }
+    }
+
+    /// Whether the scheduled pause has been reached at `now`.
+    fn is_paused(&self, now: Timestamp) -> bool {
+        self.scheduled_pause_at.is_some_and(|at| now >= at)
+    }
+
+    /// Releases what is left of the match pool, so it is paid out with the
+    /// rest of the available balance.
+    fn release_match_pool(&mut self) {
+        self.released_pool += self.match_pool;
+        self.match_pool = Amount::zero();
+    }
+
+    /// What is left available in a closed campaign if it is below
+    /// `dust_threshold`, such as the rounding left over by a rebate.
+    fn dust(&self, balance: Amount) -> Amount {
+        let available = self.available_balance(balance);
+        if self.state_of_donation == StateOfDonation::Closed && available < self.dust_threshold {
+            available
+        } else {
+            Amount::zero()
+        }
+    }
+
+    /// Whether a surplus rebate has started and not yet covered every donor.
+    fn rebate_in_progress(&self) -> bool {
+        self.rebate.as_ref().is_some_and(|rebate| rebate.cursor < self.number_of_donors)
+    }
+
+    /// Sum of the donations the donors can still take back, zero unless
+    /// refunding.
+    fn owed_refunds(&self) -> Amount {
+        if self.state_of_donation != StateOfDonation::Refunding {
+            return Amount::zero();
+        }
+        self.donations.iter().fold(Amount::zero(), |sum, (_, donated)| sum + *donated)
+    }
+
+    /// The part of the surplus still owed to the donors by a running rebate.
+    fn rebate_reserved(&self) -> Amount {
+        match self.rebate.as_ref().filter(|_| self.rebate_in_progress()) {
+            Some(rebate) => Amount::from_micro_ccd(rebate.surplus.micro_ccd.saturating_sub(rebate.rebated.micro_ccd)),
+            None => Amount::zero(),
+        }
+    }
+
+    /// The balance minus the CCD held for deferred donations, the match pool
+    /// and the part of a surplus rebate still owed. While refunding, only
+    /// what is not owed to the donors, such as the seed, is available.
+    fn available_balance(&self, balance: Amount) -> Amount {
+        let reserved = if self.state_of_donation == StateOfDonation::Refunding {
+            self.pending_total.micro_ccd.saturating_add(self.owed_refunds().micro_ccd)
+        } else {
+            self.pending_total
+                .micro_ccd
+                .saturating_add(self.match_pool.micro_ccd)
+                .saturating_add(self.rebate_reserved().micro_ccd)
+        };
+        Amount::from_micro_ccd(balance.micro_ccd.saturating_sub(reserved))
+    }
 }
 
 #[derive(Serialize, SchemaType, PartialEq, Eq, Debug, Clone, Copy)]
 enum StateOfDonation {
     Open,
     Closed,
+    /// Closed below the goal, donors can take their donations back.
+    Refunding,
 }
 
 #[derive(Serialize, SchemaType)]
 struct InitParameter {
     donation_locations: Vec<DonationLocation>,
+    location_goals: Vec<(DonationLocation, Amount)>,
     end_time: Timestamp,
+    goal: Amount,
+    trim_to_goal: bool,
+    milestones_bps: Vec<u32>,
+    recognition_threshold: Option<Amount>,
+    oracle: Option<ContractAddress>,
+    min_donation_fiat: Option<u64>,
+    donations_enabled: bool,
+    max_total: Option<Amount>,
+    auto_close_on_cap: bool,
+    queue_while_closed: bool,
+    min_funded_locations: u32,
+    max_per_donor: Option<Amount>,
+    scheduled_pause_at: Option<Timestamp>,
+    grace_period: Duration,
+    grace_donations_flagged: bool,
+    /// Owners besides the contract owner.
+    co_owners: Vec<AccountAddress>,
+    approval_threshold: u32,
+    extension_threshold: Option<Amount>,
+    extension_increment: Duration,
+    max_extension: Duration,
+    self_match_bps: u32,
+    /// Must equal the amount sent with the init call.
+    seed_amount: Amount,
+    case_insensitive_locations: bool,
+    dust_threshold: Amount,
+    refund_fee_bps: u32,
+}
+
+/// An owner action that needs `approval_threshold` approvals.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug, Clone)]
+enum OwnerAction {
+    Withdraw(WithdrawParams),
+    Close,
+    RebateSurplus,
+}
+
+/// Progress of rebating the surplus above the goal to the donors.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug, Clone)]
+struct RebateProgress {
+    /// `total_donated - goal` when the rebate started.
+    surplus: Amount,
+    /// Sum of all donor totals when the rebate started, the denominator of
+    /// each donor's share.
+    donated: Amount,
+    /// Sum of the shares rebated so far.
+    rebated: Amount,
+    /// Number of donors, in address order, that have been rebated.
+    cursor: u32,
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug, Clone)]
+struct Proposal {
+    action: OwnerAction,
+    /// Distinct owners who approved the action, in order.
+    approvals: Vec<AccountAddress>,
+}
+
+/// Events logged by the contract.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+enum DonationEvent {
+    /// A donor's cumulative total reached the recognition threshold.
+    MajorDonor {
+        donor: AccountAddress,
+        total: Amount,
+    },
+    /// The campaign was closed.
+    Closed,
+    /// A donation was counted.
+    NewDonation {
+        donor: AccountAddress,
+        location: DonationLocation,
+        amount: Amount,
+    },
+    /// The campaign was reopened.
+    Opened,
 }
 
 /// Init function that creates a new smart contract.
-#[init(contract = "donation", parameter = "InitParameter")]
+#[init(contract = "donation", parameter = "InitParameter", payable)]
 fn init<S: HasStateApi>(
     ctx: &impl HasInitContext,
-    _state_builder: &mut StateBuilder<S>,
-) -> InitResult<State> {
+    state_builder: &mut StateBuilder<S>,
+    amount: Amount,
+) -> InitResult<State<S>> {
     let param : InitParameter = ctx.parameter_cursor().get()?;
+    for location in param.donation_locations.iter() {
+        validate_str(location, MAX_LOCATION_LENGTH)?;
+    }
+    for (location, _) in param.location_goals.iter() {
+        ensure!(param.donation_locations.contains(location), Error::InvalidDonationLocation.into());
+    }
+    let owner = ctx.init_origin();
+    let mut owners = param.co_owners.clone();
+    owners.push(owner);
+    owners.sort_unstable();
+    owners.dedup();
+    ensure!(
+        param.approval_threshold >= 1 && param.approval_threshold as usize <= owners.len(),
+        Error::InvalidThreshold.into()
+    );
+    ensure!(param.self_match_bps <= 10000, Error::InvalidBasisPoints.into());
+    ensure!(param.refund_fee_bps <= 10000, Error::InvalidBasisPoints.into());
+    ensure_eq!(amount, param.seed_amount, Error::SeedMismatch.into());
 
-    Ok(State {
-        number_of_donors: 0,
-        state_of_donation: StateOfDonation::Open,
-        donation_locations: param.donation_locations,
-        end_time: param.end_time,
-    })
+    Ok(State::new(param, owner, ctx.metadata().slot_time(), state_builder))
 }
 
 /// Your smart contract errors.
 #[derive(Debug, PartialEq, Eq, Reject, Serial, SchemaType)]
+#[allow(clippy::enum_variant_names)]
 enum Error {
     /// Failed parsing the parameter.
     #[from(ParseError)]
@@ -50,6 +683,118 @@ enum Error {
     DonationHasEnded,
     DonationClosed,
     InvalidDonationLocation,
+    /// Failed logging an event.
+    #[from(LogError)]
+    LogError,
+    /// Only the owner may call this function.
+    Unauthorized,
+    /// No oracle is configured.
+    NoOracle,
+    /// Invoking the oracle failed or it returned an invalid price.
+    OracleFailed,
+    /// Failed transferring CCD.
+    #[from(TransferError)]
+    TransferFailed,
+    /// The contract balance is too low for the withdrawal.
+    InsufficientFunds,
+    /// The split amounts do not add up to the withdrawal amount.
+    SplitSumMismatch,
+    /// The campaign is view-only.
+    DonationsDisabled,
+    /// A deferred donation cannot activate after the end time.
+    InvalidActivationTime,
+    /// The donation would exceed the hard cap.
+    MaxTotalExceeded,
+    /// Too few locations have received funds to close.
+    InsufficientCoverage,
+    /// The donation would exceed the cap for a single donor.
+    DonorCapExceeded,
+    /// A string input is empty or too long.
+    InvalidString,
+    /// The goal has been locked.
+    GoalLocked,
+    /// `MAX_CHECKPOINTS` checkpoints have already been taken.
+    TooManyCheckpoints,
+    /// The donation is below the minimum.
+    BelowMinimum,
+    /// The goal has already been reached.
+    GoalReached,
+    /// The scheduled pause has been reached.
+    DonationPaused,
+    /// The approval threshold is zero or above the number of distinct owners.
+    InvalidThreshold,
+    /// A fraction in basis points is above 10000.
+    InvalidBasisPoints,
+    /// There is nothing above the goal to rebate.
+    NoSurplus,
+    /// The amount sent with the init call differs from `seed_amount`.
+    SeedMismatch,
+    /// The deferred donation has already been counted.
+    AlreadyActivated,
+    /// No deferred donation is held under the id.
+    UnknownPending,
+    /// The contract has been frozen by `finalize_forever`.
+    Finalized,
+    /// The new end time is not later than the current one.
+    InvalidEndTime,
+    /// The campaign is not refunding.
+    NotRefunding,
+    /// The donor has nothing left to take back.
+    NothingToRefund,
+    /// A surplus rebate has not covered every donor yet.
+    RebateInProgress,
+    /// Funds cannot be withdrawn while the campaign is open below the goal.
+    GoalNotReached,
+    /// Some donors have not taken their donations back yet.
+    RefundsOutstanding,
+    /// A contract sent more than is left of the goal, and the excess cannot
+    /// be returned to it.
+    ExcessFromContract,
+    /// Deferred or queued donations are still held.
+    DonationsHeld,
+    /// Funds have been paid out, so the goal cannot be raised past what was
+    /// raised.
+    FundsPaidOut,
+}
+
+#[derive(Serialize, SchemaType)]
+struct DonateParams {
+    location: DonationLocation,
+    /// If set in the future, the donation is held and only counted from then.
+    activate_at: Option<Timestamp>,
+    /// Optional label, such as "memorial", of at most `MAX_TAG_LENGTH` bytes.
+    tag: Option<String>,
+    /// Hides the donor in public views.
+    anonymous: bool,
+    /// Hides the amount in public views, while still showing the donor.
+    hide_amount: bool,
+}
+
+/// A single donation.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug, Clone)]
+struct Donation {
+    donor: AccountAddress,
+    location: DonationLocation,
+    amount: Amount,
+    tag: Option<String>,
+    /// Whether the donor is hidden in public views.
+    anonymous: bool,
+    /// Whether the amount is hidden in public views.
+    hide_amount: bool,
+}
+
+/// A held donation that has not been counted yet.
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug, Clone)]
+struct PendingDonation {
+    donation: Donation,
+    /// `None` for donations queued while closed, counted on reopen.
+    activate_at: Option<Timestamp>,
+}
+
+/// Checks that a string input is non-empty and at most `max` bytes long.
+fn validate_str(s: &str, max: usize) -> Result<(), Error> {
+    ensure!(!s.is_empty() && s.len() <= max, Error::InvalidString);
+    Ok(())
 }
 
 // Donating 
@@ -57,243 +802,4331 @@ enum Error {
     contract = "donation",
     name = "donate",
     error = "Error",
-    parameter = "DonationLocation",
+    parameter = "DonateParams",
     payable,
-    mutable
+    mutable,
+    enable_logger
 )]
 fn donate<S: HasStateApi>(
     ctx: &impl HasReceiveContext,
-    host: &mut impl HasHost<State, StateApiType = S>,
-    _amount: Amount,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+    amount: Amount,
+    logger: &mut impl HasLogger,
 ) -> Result<(), Error> {
-    // checking for the end time
-    if host.state().end_time < ctx.metadata().slot_time() {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    // checking if the campaign accepts donations at all
+    ensure!(host.state().donations_enabled, Error::DonationsDisabled);
+
+    // checking for the end time, including the grace period
+    if host.state().deadline() < ctx.metadata().slot_time() {
         return Err(Error::DonationHasEnded);
     }
 
-    // checking if donation is closed
-    if host.state().state_of_donation == StateOfDonation::Closed {
-        return Err(Error::DonationClosed);
-     }
+    // checking for the scheduled pause
+    ensure!(!host.state().is_paused(ctx.metadata().slot_time()), Error::DonationPaused);
+
+    // checking if donation is closed, unless donations are queued meanwhile
+    match host.state().state_of_donation {
+        StateOfDonation::Open => (),
+        StateOfDonation::Closed if host.state().queue_while_closed => (),
+        _ => return Err(Error::DonationClosed),
+    }
 
     // checking for the location the person is donating from
-    let donation_location: DonationLocation = ctx.parameter_cursor().get()?;
-    let _location_index = match host
-        .state()
-        .donation_locations
-        .iter()
-        .position(|location| *location == donation_location)
-        {
-            Some(idx) => idx as u32,
-            None => return Err(Error::InvalidDonationLocation),
-        };
+    let mut params: DonateParams = ctx.parameter_cursor().get()?;
+    // accounting under the canonical casing
+    params.location =
+        host.state().resolve_location(&params.location).ok_or(Error::InvalidDonationLocation)?;
 
-    Ok(())
+    // checking the fiat minimum at the current rate
+    if let Some(min_ccd) = host.state().min_donation_ccd() {
+        ensure!(amount >= min_ccd, Error::BelowMinimum);
+    }
+
+    // trimming the donation to what is left of the goal
+    let mut excess = Amount::zero();
+    let mut amount = amount;
+    if host.state().trim_to_goal {
+        let state = host.state();
+        let committed = state.raised().micro_ccd.saturating_add(state.pending_total.micro_ccd);
+        let remaining = Amount::from_micro_ccd(state.goal.micro_ccd.saturating_sub(committed));
+        ensure!(remaining > Amount::zero(), Error::GoalReached);
+        if amount > remaining {
+            // the excess goes back to the sender, which must be an account
+            ensure!(matches!(ctx.sender(), Address::Account(_)), Error::ExcessFromContract);
+            excess = amount - remaining;
+            amount = remaining;
+        }
+    }
+
+    // checking the hard cap, including donations still held and the pool
+    if let Some(max_total) = host.state().max_total {
+        let committed = host.state().received_with_pool().checked_add(host.state().pending_total);
+        let committed = committed.and_then(|committed| committed.checked_add(amount));
+        ensure!(committed.is_some_and(|committed| committed <= max_total), Error::MaxTotalExceeded);
+    }
+
+    // checking the tag
+    if let Some(tag) = &params.tag {
+        validate_str(tag, MAX_TAG_LENGTH)?;
+    }
+
+    let donor = ctx.invoker();
+
+    // checking the per-donor cap, including the donor's held donations
+    if let Some(max_per_donor) = host.state().max_per_donor {
+        let state = host.state();
+        if !state.exempt_from_donor_cap.contains(&donor) {
+            let donated = state.donations.get(&donor).map_or(Amount::zero(), |donated| *donated);
+            let held = state
+                .pending
+                .iter()
+                .filter(|(_, pending)| pending.donation.donor == donor)
+                .fold(Amount::zero(), |held, (_, pending)| held + pending.donation.amount);
+            ensure!(donated + held + amount <= max_per_donor, Error::DonorCapExceeded);
+        }
+    }
+
+    // counting new senders by kind; donations sent by a contract are
+    // credited to the account that invoked it
+    let sender = ctx.sender();
+    let state = host.state_mut();
+    if state.donor_senders.insert(sender) {
+        match sender {
+            Address::Account(_) => state.account_donors += 1,
+            Address::Contract(_) => state.contract_donors += 1,
+        }
+    }
+
+    // refunding whatever was trimmed off to the account that sent it
+    if let Address::Account(account) = sender {
+        if excess > Amount::zero() {
+            host.invoke_transfer(&account, excess)?;
+        }
+    }
+
+    let donation = Donation {
+        donor,
+        location: params.location,
+        amount,
+        tag: params.tag,
+        anonymous: params.anonymous,
+        hide_amount: params.hide_amount,
+    };
+
+    // holding deferred donations until their activation time
+    if let Some(activate_at) = params.activate_at {
+        if activate_at > ctx.metadata().slot_time() {
+            ensure!(activate_at <= host.state().end_time, Error::InvalidActivationTime);
+            host.state_mut().hold_donation(donation, Some(activate_at));
+            return Ok(());
+        }
+    }
+
+    // queueing donations to a closed campaign until it is reopened
+    if host.state().state_of_donation == StateOfDonation::Closed {
+        host.state_mut().hold_donation(donation, None);
+        return Ok(());
+    }
+
+    host.state_mut().record_donation(donation, ctx.metadata().slot_time(), logger)
 }
 
 // Closing the donation
-#[receive(contract = "donation", name = "close", mutable)]
+#[receive(contract = "donation", name = "close", mutable, enable_logger)]
 fn close<S: HasStateApi>(
     ctx: &impl HasReceiveContext,
-    host: &mut impl HasHost<State, StateApiType = S>,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+    logger: &mut impl HasLogger,
 ) -> ReceiveResult<()> {
+    ensure!(!host.state().finalized, Error::Finalized.into());
+    host.state_mut().total_calls += 1;
 
     let owner = ctx.owner();
-    let sender = ctx.sender();
-
-    ensure!(sender.matches_account(&owner));
+    let approver = match host.state().owner_account(ctx.sender()) {
+        Some(approver) => approver,
+        None => bail!(),
+    };
     ensure!(host.state().state_of_donation == StateOfDonation::Open);
 
+    // checking that enough locations have received funds
+    let funded_locations =
+        host.state().location_totals.iter().filter(|(_, total)| **total > Amount::zero()).count();
+    ensure!(
+        funded_locations >= host.state().min_funded_locations as usize,
+        Error::InsufficientCoverage.into()
+    );
+
+    // waiting for the other owners to approve
+    if !host.state_mut().approve(approver, OwnerAction::Close) {
+        return Ok(());
+    }
+
+    // letting the donors take their donations back if the goal was missed
+    if host.state().raised() < host.state().goal {
+        host.state_mut().state_of_donation = StateOfDonation::Refunding;
+        logger.log(&DonationEvent::Closed)?;
+        return Ok(());
+    }
+
     host.state_mut().state_of_donation = StateOfDonation::Closed;
+    logger.log(&DonationEvent::Closed)?;
 
-    // transfering the balance to the owner
-    let balance = host.self_balance();
-    
+    // transfering the balance to the owner, keeping deferred donations but
+    // not the match pool, which no later donation can use
+    host.state_mut().release_match_pool();
+    let balance = host.state().available_balance(host.self_balance());
+
+    host.state_mut().total_paid_out += balance;
     Ok(host.invoke_transfer(&owner, balance)?)
 }
 
-// Closing the donation
-#[receive(contract = "donation", name = "open", mutable)]
+// Returning a donor's donations after closing below the goal
+#[receive(contract = "donation", name = "refund", error = "Error", mutable)]
+fn refund<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    ensure!(host.state().state_of_donation == StateOfDonation::Refunding, Error::NotRefunding);
+
+    let donor = ctx.invoker();
+    let amount = host.state().donations.get(&donor).map_or(Amount::zero(), |donated| *donated);
+    ensure!(amount > Amount::zero(), Error::NothingToRefund);
+
+    // keeping the fee in the campaign, where the owner can withdraw it
+    let fee = Amount::from_micro_ccd(
+        (u128::from(amount.micro_ccd) * u128::from(host.state().refund_fee_bps) / 10000) as u64,
+    );
+    let refunded = amount - fee;
+
+    // zeroing rather than removing keeps the donor counted
+    let state = host.state_mut();
+    state.donations.insert(donor, Amount::zero());
+    state.total_paid_out += refunded;
+    host.invoke_transfer(&donor, refunded)?;
+    Ok(())
+}
+
+// Opening the donation
+#[receive(contract = "donation", name = "open", mutable, enable_logger)]
 fn open<S: HasStateApi>(
     ctx: &impl HasReceiveContext,
-    host: &mut impl HasHost<State, StateApiType = S>,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+    logger: &mut impl HasLogger,
 ) -> ReceiveResult<()> {
+    ensure!(!host.state().finalized, Error::Finalized.into());
+    host.state_mut().total_calls += 1;
 
     let owner = ctx.owner();
     let sender = ctx.sender();
 
     ensure!(sender.matches_account(&owner));
     ensure!(host.state().state_of_donation == StateOfDonation::Closed);
+    ensure!(!host.state().rebate_in_progress(), Error::RebateInProgress.into());
 
+    // a finished rebate is done with, a later one starts over
+    host.state_mut().rebate = None;
     host.state_mut().state_of_donation = StateOfDonation::Open;
+    logger.log(&DonationEvent::Opened)?;
+
+    // counting the donations queued while closed
+    if host.state().pending.iter().any(|(_, pending)| pending.activate_at.is_none()) {
+        let now = ctx.metadata().slot_time();
+        host.state_mut().activate_held(|pending| pending.activate_at.is_none(), now, logger)?;
+    }
     Ok(())
 }
 
-#[derive(Serialize, SchemaType)]
-struct DonationView {
-    number_donors: u32,
-    state_donation: StateOfDonation,
-    time: Timestamp,
-    balance: Amount,
-}
+// Setting the recognition threshold
+#[receive(
+    contract = "donation",
+    name = "setRecognitionThreshold",
+    error = "Error",
+    parameter = "Option<Amount>",
+    mutable
+)]
+fn set_recognition_threshold<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
 
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
 
-/// View function that returns the content of the state.
-#[receive(contract = "donation", name = "view", return_value = "DonationView")]
-fn view<S: HasStateApi>(
-    _ctx: &impl HasReceiveContext,
-    host: &impl HasHost<State, StateApiType = S>,
-) -> ReceiveResult<DonationView> {
-    let state = host.state();
-    let number_donors: u32 = state.number_of_donors.clone();
-    let state_donation: StateOfDonation = state.state_of_donation.clone();
-    let time: Timestamp = state.end_time;
-    let balance = host.self_balance();
-    Ok(DonationView {
-        number_donors,
-        state_donation,
-        time,
-        balance,
-    })
+    let threshold: Option<Amount> = ctx.parameter_cursor().get()?;
+    host.state_mut().recognition_threshold = threshold;
+    Ok(())
 }
 
+// Updating the fiat rate from the oracle
+#[receive(contract = "donation", name = "updatePriceFromOracle", error = "Error", mutable)]
+fn update_price_from_oracle<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
 
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
 
-#[concordium_cfg_test]
-mod tests {
-    use super::*;
-    use test_infrastructure::*;
+    let oracle = host.state().oracle.ok_or(Error::NoOracle)?;
 
-    const ACC: AccountAddress = AccountAddress([0u8; 32]);
+    // the stored rate is left untouched if the oracle cannot be queried
+    let mut return_value = host
+        .invoke_contract_read_only(
+            &oracle,
+            &(),
+            EntrypointName::new_unchecked("getPrice"),
+            Amount::zero(),
+        )
+        .map_err(|_| Error::OracleFailed)?
+        .ok_or(Error::OracleFailed)?;
+    let rate: u64 = return_value.get().map_err(|_| Error::OracleFailed)?;
 
-    #[test]
-    fn test_donate() {
-        // arrange
-        let mut ctx = TestReceiveContext::empty();
-        ctx.set_sender(Address::Account(ACC));
-        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
-        let donation_location = "CM";
-        let parameter = to_bytes(&donation_location);
-        ctx.set_parameter(&parameter);
-        let amount = Amount::from_micro_ccd(100);
+    host.state_mut().fiat_rate = Some(rate);
+    Ok(())
+}
 
-        let state = State {
-            number_of_donors: 0,
-            state_of_donation: StateOfDonation::Open,
-            donation_locations: vec!["GE".to_string(), "CM".to_string(), "IT".to_string(), "FR".to_string()],
-            end_time: Timestamp::from_timestamp_millis(10000),
-        };
+// Changing the goal
+#[receive(contract = "donation", name = "setGoal", error = "Error", parameter = "Amount", mutable)]
+fn set_goal<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
 
-        let mut host = TestHost::new(state, TestStateBuilder::new());
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
+    ensure!(!host.state().goal_locked, Error::GoalLocked);
 
-        // act
-        let result = donate(&ctx, &mut host, amount);
+    let goal: Amount = ctx.parameter_cursor().get()?;
+    // what has been paid out can no longer be refunded below the goal
+    let state = host.state();
+    ensure!(state.total_paid_out == Amount::zero() || goal <= state.raised(), Error::FundsPaidOut);
+    host.state_mut().goal = goal;
+    Ok(())
+}
 
-        // assert
-        assert!(result.is_ok(), "Inserting CCD results in error");
+// Moving the end time later while the donation is open
+#[receive(contract = "donation", name = "extend", error = "Error", parameter = "Timestamp", mutable)]
+fn extend<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
 
-        assert_eq!(
-            host.state().state_of_donation,
-            StateOfDonation::Open,
-            "State of donation should still be open"
-        );
-    }
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
+    ensure!(host.state().state_of_donation == StateOfDonation::Open, Error::DonationClosed);
 
-    #[test]
-    fn test_donate_wrong_location() {
-        // arrange
-        let mut ctx = TestReceiveContext::empty();
-        ctx.set_sender(Address::Account(ACC));
-        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
-        let donation_location = "USA";
-        let parameter = to_bytes(&donation_location);
-        ctx.set_parameter(&parameter);
-        let amount = Amount::from_micro_ccd(100);
+    let end_time: Timestamp = ctx.parameter_cursor().get()?;
+    ensure!(end_time > host.state().end_time, Error::InvalidEndTime);
+    host.state_mut().end_time = end_time;
+    Ok(())
+}
 
-        let state = State {
-            number_of_donors: 0,
-            state_of_donation: StateOfDonation::Open,
-            donation_locations: vec!["GE".to_string(), "CM".to_string(), "IT".to_string(), "FR".to_string()],
-            end_time: Timestamp::from_timestamp_millis(10000),
-        };
+// Locking the goal for good
+#[receive(contract = "donation", name = "lockGoal", error = "Error", mutable)]
+fn lock_goal<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
 
-        let mut host = TestHost::new(state, TestStateBuilder::new());
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
 
-        // act
-        let result = donate(&ctx, &mut host, amount);
+    host.state_mut().goal_locked = true;
+    Ok(())
+}
 
-        // assert
-        assert!(result.is_err(), "Failed due to wrong location");
-    }
+// Setting the goals of several locations at once
+#[receive(
+    contract = "donation",
+    name = "setLocationGoals",
+    error = "Error",
+    parameter = "Vec<(DonationLocation, Amount)>",
+    mutable
+)]
+fn set_location_goals<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
 
-    #[test]
-    fn test_close() {
-        // arrange
-        let mut ctx = TestReceiveContext::empty();
-        let owner = AccountAddress([0u8; 32]);
-        ctx.set_owner(owner);
-        let sender = Address::Account(owner);
-        ctx.set_sender(sender);
-        let balance = Amount::from_micro_ccd(100);
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
 
-        let state = State {
-            number_of_donors: 0,
-            state_of_donation: StateOfDonation::Open,
-            donation_locations: vec!["GE".to_string(), "CM".to_string(), "IT".to_string(), "FR".to_string()],
-            end_time: Timestamp::from_timestamp_millis(10000),
-        };
-        
+    let goals: Vec<(DonationLocation, Amount)> = ctx.parameter_cursor().get()?;
 
-        let mut host = TestHost::new(state, TestStateBuilder::new());
-        host.set_self_balance(balance);
-        // act
-        let result = close(&ctx, &mut host);
+    // rejecting the whole batch before applying any of it
+    let state = host.state_mut();
+    for (location, _) in goals.iter() {
+        ensure!(state.location_totals.get(location).is_some(), Error::InvalidDonationLocation);
+    }
+    for (location, goal) in goals {
+        state.location_goals.insert(location, goal);
+    }
+    Ok(())
+}
+
+// Recording the current total as a checkpoint
+#[receive(contract = "donation", name = "snapshotCheckpoint", error = "Error", mutable)]
+fn snapshot_checkpoint<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
+
+    let state = host.state_mut();
+    ensure!(state.checkpoints.len() < MAX_CHECKPOINTS, Error::TooManyCheckpoints);
+    state.checkpoints.push((ctx.metadata().slot_time(), state.total_donated));
+    Ok(())
+}
+
+// Exempting a donor from the per-donor cap
+#[receive(
+    contract = "donation",
+    name = "addDonorCapExemption",
+    error = "Error",
+    parameter = "AccountAddress",
+    mutable
+)]
+fn add_donor_cap_exemption<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
+
+    let donor: AccountAddress = ctx.parameter_cursor().get()?;
+    host.state_mut().exempt_from_donor_cap.insert(donor);
+    Ok(())
+}
+
+// Making a donor subject to the per-donor cap again
+#[receive(
+    contract = "donation",
+    name = "removeDonorCapExemption",
+    error = "Error",
+    parameter = "AccountAddress",
+    mutable
+)]
+fn remove_donor_cap_exemption<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
+
+    let donor: AccountAddress = ctx.parameter_cursor().get()?;
+    host.state_mut().exempt_from_donor_cap.remove(&donor);
+    Ok(())
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug, Clone)]
+struct WithdrawParams {
+    /// Total amount to withdraw.
+    amount: Amount,
+    /// How the amount is split between beneficiaries.
+    splits: Vec<(AccountAddress, Amount)>,
+}
+
+// Withdrawing to beneficiaries
+#[receive(
+    contract = "donation",
+    name = "withdraw",
+    error = "Error",
+    parameter = "WithdrawParams",
+    mutable
+)]
+fn withdraw<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    let approver = host.state().owner_account(ctx.sender()).ok_or(Error::Unauthorized)?;
+    ensure!(!host.state().rebate_in_progress(), Error::RebateInProgress);
+
+    // keeping the donations refundable until the goal is reached
+    let state = host.state();
+    ensure!(
+        state.state_of_donation != StateOfDonation::Open || state.raised() >= state.goal,
+        Error::GoalNotReached
+    );
+
+    let param: WithdrawParams = ctx.parameter_cursor().get()?;
+
+    // the splits must account for exactly the requested amount
+    let split_sum = param
+        .splits
+        .iter()
+        .try_fold(Amount::zero(), |sum, (_, amount)| sum.checked_add(*amount))
+        .ok_or(Error::SplitSumMismatch)?;
+    ensure_eq!(split_sum, param.amount, Error::SplitSumMismatch);
+    ensure!(
+        param.amount <= host.state().available_balance(host.self_balance()),
+        Error::InsufficientFunds
+    );
+
+    // waiting for the other owners to approve
+    if !host.state_mut().approve(approver, OwnerAction::Withdraw(param.clone())) {
+        return Ok(());
+    }
+
+    host.state_mut().total_paid_out += param.amount;
+    for (beneficiary, amount) in param.splits {
+        host.invoke_transfer(&beneficiary, amount)?;
+    }
+    Ok(())
+}
+
+// Closing the donation and rebating the surplus above the goal to the
+// donors, pro rata. The first call closes the campaign, pays the goal to the
+// owner and fixes the surplus, each call rebates up to the given number of
+// donors in address order. Shares are rounded down, the rounding dust is
+// swept to the owner with the last batch if it is below `dust_threshold`.
+#[receive(
+    contract = "donation",
+    name = "rebateSurplus",
+    error = "Error",
+    parameter = "u32",
+    mutable,
+    enable_logger
+)]
+fn rebate_surplus<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+    logger: &mut impl HasLogger,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    let approver = host.state().owner_account(ctx.sender()).ok_or(Error::Unauthorized)?;
+    let batch: u32 = ctx.parameter_cursor().get()?;
+
+    if host.state().rebate.is_none() {
+        let state = host.state();
+        ensure!(state.state_of_donation == StateOfDonation::Open, Error::DonationClosed);
+        ensure!(state.total_donated > state.goal, Error::NoSurplus);
+        let surplus = state.total_donated - state.goal;
+        let donated = state.donations.iter().fold(Amount::zero(), |sum, (_, total)| sum + *total);
+
+        // waiting for the other owners to approve
+        if !host.state_mut().approve(approver, OwnerAction::RebateSurplus) {
+            return Ok(());
+        }
+
+        host.state_mut().release_match_pool();
+        let to_owner = Amount::from_micro_ccd(
+            host.state().available_balance(host.self_balance()).micro_ccd.saturating_sub(surplus.micro_ccd),
+        );
+        let state = host.state_mut();
+        state.state_of_donation = StateOfDonation::Closed;
+        state.total_paid_out += to_owner;
+        state.rebate = Some(RebateProgress {
+            surplus,
+            donated,
+            rebated: Amount::zero(),
+            cursor: 0,
+        });
+        logger.log(&DonationEvent::Closed)?;
+        host.invoke_transfer(&ctx.owner(), to_owner)?;
+    }
+
+    let progress = host.state().rebate.clone().ok_or(Error::NoSurplus)?;
+    let mut donors: Vec<(AccountAddress, Amount)> =
+        host.state().donations.iter().map(|(donor, total)| (*donor, *total)).collect();
+    donors.sort_by_key(|(donor, _)| *donor);
+    // never rebating more than the surplus, even to donors counted later
+    let mut left = progress.surplus.micro_ccd.saturating_sub(progress.rebated.micro_ccd);
+    let rebates: Vec<(AccountAddress, Amount)> = donors
+        .into_iter()
+        .skip(progress.cursor as usize)
+        .take(batch as usize)
+        .map(|(donor, total)| {
+            let share = u128::from(progress.surplus.micro_ccd) * u128::from(total.micro_ccd)
+                / u128::from(progress.donated.micro_ccd);
+            let share = (share as u64).min(left);
+            left -= share;
+            (donor, Amount::from_micro_ccd(share))
+        })
+        .collect();
+
+    let state = host.state_mut();
+    for (_, share) in rebates.iter() {
+        state.total_paid_out += *share;
+    }
+    if let Some(rebate) = state.rebate.as_mut() {
+        rebate.cursor += rebates.len() as u32;
+        rebate.rebated = rebate.surplus - Amount::from_micro_ccd(left);
+    }
+    for (donor, share) in rebates {
+        host.invoke_transfer(&donor, share)?;
+    }
+
+    // sweeping the rounding dust once every donor has been rebated
+    if !host.state().rebate_in_progress() {
+        let dust = host.state().dust(host.self_balance());
+        if dust > Amount::zero() {
+            host.state_mut().total_paid_out += dust;
+            host.invoke_transfer(&ctx.owner(), dust)?;
+        }
+    }
+    Ok(())
+}
+
+// Freezing the closed campaign for good; every mutable entrypoint rejects
+// with `Finalized` afterwards, while views keep working. A refunding campaign
+// can only be frozen once every donor has been refunded.
+#[receive(contract = "donation", name = "finalizeForever", error = "Error", mutable)]
+fn finalize_forever<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    ensure!(ctx.sender().matches_account(&ctx.owner()), Error::Unauthorized);
+    match host.state().state_of_donation {
+        StateOfDonation::Open => return Err(Error::DonationClosed),
+        StateOfDonation::Closed => (),
+        StateOfDonation::Refunding => {
+            ensure!(host.state().owed_refunds() == Amount::zero(), Error::RefundsOutstanding)
+        }
+    }
+    ensure!(!host.state().rebate_in_progress(), Error::RebateInProgress);
+    // held donations could no longer be cancelled or counted
+    ensure!(host.state().pending_total == Amount::zero(), Error::DonationsHeld);
+
+    host.state_mut().finalized = true;
+    Ok(())
+}
+
+// Activating deferred donations whose time has come
+#[receive(contract = "donation", name = "activatePending", error = "Error", mutable, enable_logger)]
+fn activate_pending<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+    logger: &mut impl HasLogger,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    let now = ctx.metadata().slot_time();
+    host.state_mut().activate_held(
+        |pending| pending.activate_at.is_some_and(|activate_at| activate_at <= now),
+        now,
+        logger,
+    )
+}
+
+// Cancelling a held donation before it is counted, returning the CCD to the
+// donor. Donations whose activation time has passed count as activated even
+// before `activatePending` is called.
+#[receive(contract = "donation", name = "cancelDeferred", error = "Error", parameter = "u64", mutable)]
+fn cancel_deferred<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &mut impl HasHost<State<S>, StateApiType = S>,
+) -> Result<(), Error> {
+    ensure!(!host.state().finalized, Error::Finalized);
+    host.state_mut().total_calls += 1;
+
+    let id: u64 = ctx.parameter_cursor().get()?;
+    let pending = match host.state().pending.get(&id) {
+        Some(pending) => pending.clone(),
+        None if host.state().cancelled_pending.contains(&id) => return Err(Error::UnknownPending),
+        None if id < host.state().next_pending_id => return Err(Error::AlreadyActivated),
+        None => return Err(Error::UnknownPending),
+    };
+    ensure!(pending.donation.donor == ctx.invoker(), Error::Unauthorized);
+    let now = ctx.metadata().slot_time();
+    ensure!(
+        pending.activate_at.is_none_or(|activate_at| activate_at > now),
+        Error::AlreadyActivated
+    );
+
+    let state = host.state_mut();
+    state.pending.remove(&id);
+    state.cancelled_pending.insert(id);
+    state.pending_total -= pending.donation.amount;
+    host.invoke_transfer(&pending.donation.donor, pending.donation.amount)?;
+    Ok(())
+}
+
+/// View function that returns the deferred donations that are not active yet,
+/// ordered by id. The map iterates in the order of the serialized ids, which
+/// is not numeric, so the entries are sorted.
+#[receive(
+    contract = "donation",
+    name = "viewPending",
+    return_value = "Vec<(u64, PendingDonation)>"
+)]
+fn view_pending<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Vec<(u64, PendingDonation)>> {
+    let mut pending: Vec<(u64, PendingDonation)> =
+        host.state().pending.iter().map(|(id, pending)| (*id, pending.clone())).collect();
+    pending.sort_by_key(|(id, _)| *id);
+    Ok(pending)
+}
+
+#[derive(Serialize, SchemaType)]
+struct DonationView {
+    number_donors: u32,
+    state_donation: StateOfDonation,
+    time: Timestamp,
+    balance: Amount,
+}
+
+
+/// View function that returns the content of the state.
+#[receive(contract = "donation", name = "view", return_value = "DonationView")]
+fn view<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<DonationView> {
+    let state = host.state();
+    let number_donors: u32 = state.number_of_donors;
+    let state_donation: StateOfDonation = state.state_of_donation;
+    let time: Timestamp = state.end_time;
+    let balance = host.self_balance();
+    Ok(DonationView {
+        number_donors,
+        state_donation,
+        time,
+        balance,
+    })
+}
+
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct ProgressView {
+    total_donated: Amount,
+    fiat_rate: Option<u64>,
+    /// `total_donated` in fiat cents, if a rate is known.
+    total_fiat: Option<u64>,
+}
+
+/// View function that returns the donated total, in CCD and fiat.
+#[receive(contract = "donation", name = "viewProgress", return_value = "ProgressView")]
+fn view_progress<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<ProgressView> {
+    let state = host.state();
+    let total_fiat = state.fiat_rate.map(|rate| to_fiat(state.total_donated, rate));
+    Ok(ProgressView {
+        total_donated: state.total_donated,
+        fiat_rate: state.fiat_rate,
+        total_fiat,
+    })
+}
+
+/// Converts an amount to fiat cents at the given rate (fiat cents per CCD).
+fn to_fiat(amount: Amount, rate: u64) -> u64 {
+    let cents = u128::from(amount.micro_ccd) * u128::from(rate) / 1_000_000;
+    cents.min(u128::from(u64::MAX)) as u64
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct LocationView {
+    location: DonationLocation,
+    total: Amount,
+    /// Number of distinct donors of the location.
+    donors: u32,
+    /// `None` if the location has no goal of its own.
+    goal: Option<Amount>,
+    goal_reached: bool,
+}
+
+/// View function that returns the total, donors and goal of every location,
+/// in the order of `donation_locations`.
+#[receive(contract = "donation", name = "viewAllLocations", return_value = "Vec<LocationView>")]
+fn view_all_locations<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Vec<LocationView>> {
+    let state = host.state();
+    Ok(state.donation_locations.iter().map(|location| state.location_view(location)).collect())
+}
+
+/// View function that returns the breakdown of every location, the same as
+/// `view_all_locations`.
+#[receive(contract = "donation", name = "viewLocations", return_value = "Vec<LocationView>")]
+fn view_locations<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Vec<LocationView>> {
+    view_all_locations(ctx, host)
+}
+
+/// View function that returns the total and goal of a location.
+#[receive(
+    contract = "donation",
+    name = "viewLocation",
+    error = "Error",
+    parameter = "DonationLocation",
+    return_value = "LocationView"
+)]
+fn view_location<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> Result<LocationView, Error> {
+    let location: DonationLocation = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    let location = state.resolve_location(&location).ok_or(Error::InvalidDonationLocation)?;
+    Ok(state.location_view(&location))
+}
+
+/// View function that returns whether donations can be made to a location.
+#[receive(
+    contract = "donation",
+    name = "viewLocationExists",
+    parameter = "DonationLocation",
+    return_value = "bool"
+)]
+fn view_location_exists<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<bool> {
+    let location: DonationLocation = ctx.parameter_cursor().get()?;
+    Ok(host.state().resolve_location(&location).is_some())
+}
+
+/// View function that returns each location's share of everything received,
+/// late donations included, in basis points, in the order of
+/// `donation_locations`.
+///
+/// Shares are rounded down and the basis points lost to rounding are handed
+/// out one each to the locations with the largest remainders (earlier
+/// locations first on ties), so the shares always sum to 10000 once anything
+/// has been donated.
+#[receive(
+    contract = "donation",
+    name = "viewLocationShares",
+    return_value = "Vec<(DonationLocation, u32)>"
+)]
+fn view_location_shares<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Vec<(DonationLocation, u32)>> {
+    let state = host.state();
+    let total = u128::from(state.total_received().micro_ccd);
+    if total == 0 {
+        return Ok(state.donation_locations.iter().map(|location| (location.clone(), 0)).collect());
+    }
+
+    let mut shares = Vec::with_capacity(state.donation_locations.len());
+    let mut remainders = Vec::with_capacity(state.donation_locations.len());
+    for (index, location) in state.donation_locations.iter().enumerate() {
+        let location_total = state.location_totals.get(location).map_or(0, |amount| amount.micro_ccd);
+        let scaled = u128::from(location_total) * 10000;
+        shares.push((location.clone(), (scaled / total) as u32));
+        remainders.push((scaled % total, index));
+    }
+
+    let distributed: u32 = shares.iter().map(|(_, share)| share).sum();
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for (_, index) in remainders.into_iter().take(10000u32.saturating_sub(distributed) as usize) {
+        shares[index].1 += 1;
+    }
+    Ok(shares)
+}
+
+/// View function that returns the total donated under a tag.
+#[receive(contract = "donation", name = "viewByTag", parameter = "String", return_value = "Amount")]
+fn view_by_tag<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Amount> {
+    let tag: String = ctx.parameter_cursor().get()?;
+    Ok(host.state().tag_totals.get(&tag).map_or(Amount::zero(), |total| *total))
+}
+
+/// View function that returns up to the given number of tags with the largest
+/// totals, largest first and ties ordered by tag. At most `MAX_TOP_TAGS`
+/// entries are returned.
+#[receive(
+    contract = "donation",
+    name = "viewTopTags",
+    parameter = "u32",
+    return_value = "Vec<(String, Amount)>"
+)]
+fn view_top_tags<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Vec<(String, Amount)>> {
+    let count: u32 = ctx.parameter_cursor().get()?;
+    let mut tags: Vec<(String, Amount)> =
+        host.state().tag_totals.iter().map(|(tag, total)| (tag.clone(), *total)).collect();
+    tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    tags.truncate(count.min(MAX_TOP_TAGS) as usize);
+    Ok(tags)
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct RecentDonation {
+    /// `None` for anonymous donations.
+    donor: Option<AccountAddress>,
+    location: DonationLocation,
+    /// `None` if the donor hid the amount.
+    amount: Option<Amount>,
+    time: Timestamp,
+}
+
+/// View function that returns up to the given number of the most recent
+/// donations, newest first. At most `MAX_RECENT_DONATIONS` entries are
+/// returned.
+#[receive(
+    contract = "donation",
+    name = "viewRecentDonations",
+    parameter = "u32",
+    return_value = "Vec<RecentDonation>"
+)]
+fn view_recent_donations<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Vec<RecentDonation>> {
+    let count: u32 = ctx.parameter_cursor().get()?;
+    Ok(host
+        .state()
+        .recent_donations
+        .iter()
+        .rev()
+        .take(count.min(MAX_RECENT_DONATIONS) as usize)
+        .map(|(donation, time)| {
+            let donor = if donation.anonymous {
+                None
+            } else {
+                Some(donation.donor)
+            };
+            let amount = if donation.hide_amount {
+                None
+            } else {
+                Some(donation.amount)
+            };
+            RecentDonation {
+                donor,
+                location: donation.location.clone(),
+                amount,
+                time: *time,
+            }
+        })
+        .collect())
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct LeaderboardEntry {
+    /// `None` for donors who made an anonymous donation.
+    donor: Option<AccountAddress>,
+    /// `None` for donors who hid the amount of a donation.
+    total: Option<Amount>,
+}
+
+/// View function that returns up to the given number of donors with the
+/// largest totals, largest first and ties ordered by address. Hidden totals
+/// are still used for the ordering. At most `MAX_LEADERBOARD` entries are
+/// returned.
+#[receive(
+    contract = "donation",
+    name = "viewLeaderboard",
+    parameter = "u32",
+    return_value = "Vec<LeaderboardEntry>"
+)]
+fn view_leaderboard<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Vec<LeaderboardEntry>> {
+    let count: u32 = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    Ok(state
+        .ranked_donors()
+        .into_iter()
+        .take(count.min(MAX_LEADERBOARD) as usize)
+        .map(|(donor, total)| LeaderboardEntry {
+            donor: if state.anonymous_donors.contains(&donor) {
+                None
+            } else {
+                Some(donor)
+            },
+            total: if state.hidden_amount_donors.contains(&donor) {
+                None
+            } else {
+                Some(total)
+            },
+        })
+        .collect())
+}
+
+/// View function that returns the 1-based position of a donor in the order of
+/// `view_leaderboard`, or `None` if they have not donated. Ties are ordered by
+/// address, so every donor has a distinct rank.
+#[receive(
+    contract = "donation",
+    name = "viewDonorRank",
+    parameter = "AccountAddress",
+    return_value = "Option<u32>"
+)]
+fn view_donor_rank<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Option<u32>> {
+    let donor: AccountAddress = ctx.parameter_cursor().get()?;
+    Ok(host
+        .state()
+        .ranked_donors()
+        .iter()
+        .position(|(ranked, _)| *ranked == donor)
+        .map(|index| index as u32 + 1))
+}
+
+/// View function that returns the number of successful calls to mutable
+/// entrypoints, as a rough measure of activity.
+#[receive(contract = "donation", name = "viewActivity", return_value = "u64")]
+fn view_activity<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<u64> {
+    Ok(host.state().total_calls)
+}
+
+/// View function that returns the checkpoints taken, oldest first.
+#[receive(contract = "donation", name = "viewCheckpoints", return_value = "Vec<(Timestamp, Amount)>")]
+fn view_checkpoints<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Vec<(Timestamp, Amount)>> {
+    Ok(host.state().checkpoints.clone())
+}
+
+/// View function that returns the time-weighted average donation.
+///
+/// Each donation `a_i` is weighted by the milliseconds `w_i = end_time - t_i`
+/// left in the campaign at the time `t_i` it was counted, so earlier support
+/// weighs more:
+///
+/// `twa = sum(a_i * w_i) / sum(w_i)`
+///
+/// Weights are fixed when a donation is counted and are not updated if
+/// `end_time` changes later. Donations counted at or after `end_time` carry
+/// no weight, and the result is zero when no donation carries any weight.
+#[receive(contract = "donation", name = "viewTwa", return_value = "Amount")]
+fn view_twa<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Amount> {
+    let state = host.state();
+    let twa = state.weighted_amount_sum.checked_div(state.weight_sum).unwrap_or(0);
+    Ok(Amount::from_micro_ccd(twa.min(u128::from(u64::MAX)) as u64))
+}
+
+/// View function that returns whether donating the given amount would bring
+/// `total_donated` up to the goal.
+#[receive(
+    contract = "donation",
+    name = "viewWouldCompleteGoal",
+    parameter = "Amount",
+    return_value = "bool"
+)]
+fn view_would_complete_goal<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<bool> {
+    let amount: Amount = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    // an overflowing total is certainly past the goal
+    Ok(state.total_donated.checked_add(amount).is_none_or(|total| total >= state.goal))
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct StatsView {
+    number_of_donors: u32,
+    /// Distinct accounts that donated directly.
+    account_donors: u32,
+    /// Distinct contracts that donated.
+    contract_donors: u32,
+    total_donated: Amount,
+}
+
+/// View function that returns the donor counts and the donated total.
+#[receive(contract = "donation", name = "viewStats", return_value = "StatsView")]
+fn view_stats<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<StatsView> {
+    let state = host.state();
+    Ok(StatsView {
+        number_of_donors: state.number_of_donors,
+        account_donors: state.account_donors,
+        contract_donors: state.contract_donors,
+        total_donated: state.total_donated,
+    })
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct NextMilestone {
+    /// The milestone as a fraction of the goal in basis points.
+    milestone_bps: u32,
+    /// Total at which the milestone is reached.
+    amount: Amount,
+    /// What is left to donate to reach it.
+    gap: Amount,
+}
+
+/// View function that returns the lowest milestone not reached yet, or `None`
+/// once all are reached.
+#[receive(contract = "donation", name = "viewNextMilestone", return_value = "Option<NextMilestone>")]
+fn view_next_milestone<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Option<NextMilestone>> {
+    let state = host.state();
+    let next = state
+        .milestones_bps
+        .iter()
+        .map(|milestone_bps| {
+            let amount = u128::from(state.goal.micro_ccd) * u128::from(*milestone_bps) / 10000;
+            (*milestone_bps, Amount::from_micro_ccd(amount.min(u128::from(u64::MAX)) as u64))
+        })
+        .find(|(_, amount)| *amount > state.total_donated)
+        .map(|(milestone_bps, amount)| NextMilestone {
+            milestone_bps,
+            amount,
+            gap: amount - state.total_donated,
+        });
+    Ok(next)
+}
+
+/// View function that returns a naive linear projection of the total at
+/// `end_time`.
+///
+/// The velocity is the average rate since `start_time`, i.e.
+/// `total_donated / (now - start_time)`, and the projection is
+/// `total_donated + velocity * (end_time - now)`. It does not account for
+/// donations slowing down or speeding up and is only meant as a rough guide.
+#[receive(contract = "donation", name = "viewProjection", return_value = "Amount")]
+fn view_projection<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Amount> {
+    let state = host.state();
+    let now = ctx.metadata().slot_time();
+    let elapsed = now.timestamp_millis().saturating_sub(state.start_time.timestamp_millis());
+    let remaining = state.end_time.timestamp_millis().saturating_sub(now.timestamp_millis());
+    if elapsed == 0 {
+        return Ok(state.total_donated);
+    }
+
+    let total = u128::from(state.total_donated.micro_ccd);
+    let projected = total + total * u128::from(remaining) / u128::from(elapsed);
+    Ok(Amount::from_micro_ccd(projected.min(u128::from(u64::MAX)) as u64))
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct SizeReport {
+    donors: u32,
+    locations: u32,
+    pending: u32,
+    tags: u32,
+    recent_donations: u32,
+    checkpoints: u32,
+}
+
+/// View function that returns the sizes of the collections in the state, so
+/// clients can tell when a view may run out of energy and page instead.
+/// Events are logged, not stored, so they do not grow the state.
+#[receive(contract = "donation", name = "viewSizeReport", return_value = "SizeReport")]
+fn view_size_report<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<SizeReport> {
+    let state = host.state();
+    Ok(SizeReport {
+        donors: state.number_of_donors,
+        locations: state.donation_locations.len() as u32,
+        pending: state.pending.iter().count() as u32,
+        tags: state.tag_totals.iter().count() as u32,
+        recent_donations: state.recent_donations.len() as u32,
+        checkpoints: state.checkpoints.len() as u32,
+    })
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct NetPosition {
+    balance: Amount,
+    /// CCD held for deferred and queued donations.
+    held: Amount,
+    /// CCD set aside to match later donations. While refunding it is owed
+    /// back as part of `owed_refunds` instead.
+    match_pool: Amount,
+    /// Donations the donors can still take back while refunding.
+    owed_refunds: Amount,
+    /// The part of the surplus a running rebate still owes.
+    rebate_reserved: Amount,
+    /// `balance` minus the held CCD and the other obligations, what the
+    /// owner can actually take out.
+    net: Amount,
+}
+
+/// View function that returns the balance of the contract net of the CCD it
+/// holds on behalf of donors, the match pool, refunds and rebates still owed.
+#[receive(contract = "donation", name = "viewNetPosition", return_value = "NetPosition")]
+fn view_net_position<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<NetPosition> {
+    let balance = host.self_balance();
+    Ok(NetPosition {
+        balance,
+        held: host.state().pending_total,
+        match_pool: host.state().match_pool,
+        owed_refunds: host.state().owed_refunds(),
+        rebate_reserved: host.state().rebate_reserved(),
+        net: host.state().available_balance(balance),
+    })
+}
+
+/// View function that returns the dust a closed campaign would sweep to the
+/// owner, zero if the remainder is at least `dust_threshold`.
+#[receive(contract = "donation", name = "viewDust", return_value = "Amount")]
+fn view_dust<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Amount> {
+    Ok(host.state().dust(host.self_balance()))
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct InvariantsView {
+    /// `number_of_donors` equals the number of recorded donors.
+    donor_count: bool,
+    /// The location totals add up to `total_donated`.
+    location_totals: bool,
+    /// The balance equals what was donated or held, minus what was paid out.
+    balance: bool,
+    /// All of the above hold.
+    consistent: bool,
+}
+
+/// View function that checks the internal consistency of the state.
+#[receive(contract = "donation", name = "viewCheckInvariants", return_value = "InvariantsView")]
+fn view_check_invariants<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<InvariantsView> {
+    let state = host.state();
+    let donor_count = state.donations.iter().count() == state.number_of_donors as usize;
+    let location_sum = state
+        .location_totals
+        .iter()
+        .try_fold(Amount::zero(), |sum, (_, total)| sum.checked_add(*total));
+    let location_totals = location_sum == Some(state.total_received());
+    let expected_balance = state
+        .total_received()
+        .checked_add(state.pending_total)
+        .and_then(|received| received.checked_add(state.match_pool))
+        .and_then(|received| received.checked_add(state.released_pool))
+        .and_then(|received| received.checked_add(state.seed_balance))
+        .and_then(|received| received.micro_ccd.checked_sub(state.total_paid_out.micro_ccd));
+    let balance = expected_balance == Some(host.self_balance().micro_ccd);
+    Ok(InvariantsView {
+        donor_count,
+        location_totals,
+        balance,
+        consistent: donor_count && location_totals && balance,
+    })
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+enum CampaignStatus {
+    Open,
+    /// The scheduled pause time has passed.
+    Paused,
+    Closed,
+    /// The end time and grace period have passed.
+    Ended,
+    /// Closed below the goal, donors can take their donations back.
+    Refunding,
+}
+
+/// View function that returns the status of the campaign at the current time.
+#[receive(contract = "donation", name = "viewStatus", return_value = "CampaignStatus")]
+fn view_status<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<CampaignStatus> {
+    let state = host.state();
+    let now = ctx.metadata().slot_time();
+    Ok(if state.state_of_donation == StateOfDonation::Refunding {
+        CampaignStatus::Refunding
+    } else if state.deadline() < now {
+        CampaignStatus::Ended
+    } else if state.state_of_donation == StateOfDonation::Closed {
+        CampaignStatus::Closed
+    } else if state.is_paused(now) {
+        CampaignStatus::Paused
+    } else {
+        CampaignStatus::Open
+    })
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct EffectiveDeadline {
+    end_time: Timestamp,
+    grace_period: Duration,
+    /// `end_time + grace_period`, `None` if that overflows, in which case
+    /// donations are accepted indefinitely.
+    deadline: Option<Timestamp>,
+}
+
+/// View function that returns the end time and the last time at which
+/// donations are accepted, including the grace period.
+#[receive(contract = "donation", name = "viewEffectiveDeadline", return_value = "EffectiveDeadline")]
+fn view_effective_deadline<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<EffectiveDeadline> {
+    let state = host.state();
+    Ok(EffectiveDeadline {
+        end_time: state.end_time,
+        grace_period: state.grace_period,
+        deadline: state.end_time.checked_add(state.grace_period),
+    })
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct EventFieldSchema {
+    name: String,
+    /// Name of the field's type, e.g. `Amount`.
+    type_name: String,
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct EventVariantSchema {
+    name: String,
+    fields: Vec<EventFieldSchema>,
+}
+
+/// Describes a variant of `DonationEvent` with its named fields.
+fn event_variant(name: &str, fields: &[(&str, &str)]) -> EventVariantSchema {
+    EventVariantSchema {
+        name: name.to_string(),
+        fields: fields
+            .iter()
+            .map(|(name, type_name)| EventFieldSchema {
+                name: name.to_string(),
+                type_name: type_name.to_string(),
+            })
+            .collect(),
+    }
+}
+
+/// View function that returns the variants of `DonationEvent` with their
+/// fields, in tag order. Must be updated whenever an event is added or
+/// changed.
+#[receive(contract = "donation", name = "viewEventSchema", return_value = "Vec<EventVariantSchema>")]
+fn view_event_schema<S: HasStateApi>(
+    _ctx: &impl HasReceiveContext,
+    _host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<Vec<EventVariantSchema>> {
+    Ok(vec![
+        event_variant("MajorDonor", &[("donor", "AccountAddress"), ("total", "Amount")]),
+        event_variant("Closed", &[]),
+        event_variant(
+            "NewDonation",
+            &[("donor", "AccountAddress"), ("location", "DonationLocation"), ("amount", "Amount")],
+        ),
+        event_variant("Opened", &[]),
+    ])
+}
+
+#[derive(Serialize, SchemaType)]
+enum AdminAction {
+    Close,
+    Open,
+    Withdraw,
+    AddLocation,
+}
+
+#[derive(Serialize, SchemaType)]
+struct CanParams {
+    account: AccountAddress,
+    action: AdminAction,
+}
+
+/// View function that returns whether an account is authorized to perform an
+/// admin action, regardless of whether the action is currently possible.
+/// Any owner can approve `Close` and `Withdraw`, only the contract owner can
+/// `Open`. Locations are fixed at init, so `AddLocation` is never allowed.
+#[receive(contract = "donation", name = "viewCan", parameter = "CanParams", return_value = "bool")]
+fn view_can<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<bool> {
+    let params: CanParams = ctx.parameter_cursor().get()?;
+    Ok(match params.action {
+        AdminAction::Close | AdminAction::Withdraw => host.state().owners.contains(&params.account),
+        AdminAction::Open => params.account == ctx.owner(),
+        AdminAction::AddLocation => false,
+    })
+}
+
+/// Version of the `ExplorerView` layout. Bumped whenever a field is added,
+/// removed or changes meaning, so indexers can detect schema changes.
+const EXPLORER_VIEW_VERSION: u32 = 3;
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct ExplorerConfig {
+    donation_locations: Vec<DonationLocation>,
+    start_time: Timestamp,
+    end_time: Timestamp,
+    goal: Amount,
+    donations_enabled: bool,
+    max_total: Option<Amount>,
+    auto_close_on_cap: bool,
+    max_per_donor: Option<Amount>,
+    min_funded_locations: u32,
+    recognition_threshold: Option<Amount>,
+    oracle: Option<ContractAddress>,
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct ExplorerStats {
+    number_of_donors: u32,
+    total_donated: Amount,
+    pending_total: Amount,
+    balance: Amount,
+    fiat_rate: Option<u64>,
+    /// Totals per location, in the order of `donation_locations`.
+    location_totals: Vec<(DonationLocation, Amount)>,
+}
+
+#[derive(Serialize, SchemaType, PartialEq, Eq, Debug)]
+struct ExplorerView {
+    /// Always `EXPLORER_VIEW_VERSION`.
+    version: u32,
+    config: ExplorerConfig,
+    stats: ExplorerStats,
+    /// Account receiving the funds on close.
+    recipient: AccountAddress,
+    status: StateOfDonation,
+}
+
+/// View function that returns everything an explorer needs in one call.
+#[receive(contract = "donation", name = "viewExplorer", return_value = "ExplorerView")]
+fn view_explorer<S: HasStateApi>(
+    ctx: &impl HasReceiveContext,
+    host: &impl HasHost<State<S>, StateApiType = S>,
+) -> ReceiveResult<ExplorerView> {
+    let state = host.state();
+    let location_totals = state
+        .donation_locations
+        .iter()
+        .map(|location| {
+            let total = state.location_totals.get(location).map_or(Amount::zero(), |total| *total);
+            (location.clone(), total)
+        })
+        .collect();
+
+    Ok(ExplorerView {
+        version: EXPLORER_VIEW_VERSION,
+        config: ExplorerConfig {
+            donation_locations: state.donation_locations.clone(),
+            start_time: state.start_time,
+            end_time: state.end_time,
+            goal: state.goal,
+            donations_enabled: state.donations_enabled,
+            max_total: state.max_total,
+            auto_close_on_cap: state.auto_close_on_cap,
+            max_per_donor: state.max_per_donor,
+            min_funded_locations: state.min_funded_locations,
+            recognition_threshold: state.recognition_threshold,
+            oracle: state.oracle,
+        },
+        stats: ExplorerStats {
+            number_of_donors: state.number_of_donors,
+            total_donated: state.total_donated,
+            pending_total: state.pending_total,
+            balance: host.self_balance(),
+            fiat_rate: state.fiat_rate,
+            location_totals,
+        },
+        recipient: ctx.owner(),
+        status: state.state_of_donation,
+    })
+}
+
+#[concordium_cfg_test]
+mod tests {
+    use super::*;
+    use test_infrastructure::*;
+
+    const ACC: AccountAddress = AccountAddress([0u8; 32]);
+    const ORACLE: ContractAddress = ContractAddress {
+        index: 1,
+        subindex: 0,
+    };
+
+    fn init_parameter() -> InitParameter {
+        InitParameter {
+            donation_locations: vec!["GE".to_string(), "CM".to_string(), "IT".to_string(), "FR".to_string()],
+            location_goals: Vec::new(),
+            end_time: Timestamp::from_timestamp_millis(10000),
+            goal: Amount::zero(),
+            trim_to_goal: false,
+            milestones_bps: vec![2500, 5000, 7500, 10000],
+            recognition_threshold: None,
+            oracle: None,
+            min_donation_fiat: None,
+            donations_enabled: true,
+            max_total: None,
+            auto_close_on_cap: false,
+            queue_while_closed: false,
+            min_funded_locations: 0,
+            max_per_donor: None,
+            scheduled_pause_at: None,
+            grace_period: Duration::from_millis(0),
+            grace_donations_flagged: false,
+            co_owners: Vec::new(),
+            approval_threshold: 1,
+            extension_threshold: None,
+            extension_increment: Duration::from_millis(0),
+            max_extension: Duration::from_millis(0),
+            self_match_bps: 0,
+            seed_amount: Amount::zero(),
+            case_insensitive_locations: false,
+            dust_threshold: Amount::zero(),
+            refund_fee_bps: 0,
+        }
+    }
+
+    fn test_state(state_builder: &mut TestStateBuilder) -> State<TestStateApi> {
+        State::new(init_parameter(), ACC, Timestamp::from_timestamp_millis(0), state_builder)
+    }
+
+    fn donate_params(location: &str) -> DonateParams {
+        DonateParams {
+            location: location.to_string(),
+            activate_at: None,
+            tag: None,
+            anonymous: false,
+            hide_amount: false,
+        }
+    }
+
+    fn donate_as(
+        host: &mut TestHost<State<TestStateApi>>,
+        donor: AccountAddress,
+        location: &str,
+        amount: Amount,
+    ) -> Result<(), Error> {
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(donor));
+        ctx.set_invoker(donor);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params(location));
+        ctx.set_parameter(&parameter);
+        donate(&ctx, host, amount, &mut TestLogger::init())
+    }
+
+    #[test]
+    fn test_donate() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&parameter);
+        let amount = Amount::from_micro_ccd(100);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        let mut logger = TestLogger::init();
+
+        // act
+        let result = donate(&ctx, &mut host, amount, &mut logger);
+
+        // assert
+        assert!(result.is_ok(), "Inserting CCD results in error");
+
+        assert_eq!(
+            host.state().state_of_donation,
+            StateOfDonation::Open,
+            "State of donation should still be open"
+        );
+        assert_eq!(host.state().number_of_donors, 1, "Donor should be counted");
+        assert_eq!(
+            host.state().location_totals.get(&"CM".to_string()).map(|total| *total),
+            Some(amount),
+            "Location total should equal the donated amount"
+        );
+        assert_eq!(
+            view(&ctx, &host).map(|view| view.number_donors),
+            Ok(1),
+            "View should report the donor count"
+        );
+    }
+
+    #[test]
+    fn test_donate_wrong_location() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("USA"));
+        ctx.set_parameter(&parameter);
+        let amount = Amount::from_micro_ccd(100);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        let mut logger = TestLogger::init();
+
+        // act
+        let result = donate(&ctx, &mut host, amount, &mut logger);
+
+        // assert
+        assert!(result.is_err(), "Failed due to wrong location");
+    }
+
+    #[test]
+    fn test_close() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        let owner = AccountAddress([0u8; 32]);
+        ctx.set_owner(owner);
+        let sender = Address::Account(owner);
+        ctx.set_sender(sender);
+        let balance = Amount::from_micro_ccd(100);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(balance);
+        // act
+        let result = close(&ctx, &mut host, &mut TestLogger::init());
+
+        // assert
+        assert!(result.is_ok(), "Failed to close donation.");
+        assert_eq!(host.state().state_of_donation, StateOfDonation::Closed, "State of donation should be closed.");
+        assert_eq!(
+            host.get_transfers(),
+            [(owner, balance)],
+            "wrong transfers."
+        );
+    }
+
+    #[test]
+    fn test_open() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        let owner = AccountAddress([0u8; 32]);
+        ctx.set_owner(owner);
+        let sender = Address::Account(owner);
+        ctx.set_sender(sender);
+        let balance = Amount::from_micro_ccd(100);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(balance);
+        // act
+        let result = close(&ctx, &mut host, &mut TestLogger::init());
+
+        // assert
+        assert!(result.is_ok(), "Failed to close donation.");
+        assert_eq!(host.state().state_of_donation, StateOfDonation::Closed, "State of donation should be closed.");
+        assert_eq!(
+            host.get_transfers(),
+            [(owner, balance)],
+            "wrong transfers."
+        );
+
+        // open
+        let open_result = open(&ctx, &mut host, &mut TestLogger::init());
+        assert!(open_result.is_ok(), "Failed to open donation.");
+        assert_eq!(host.state().state_of_donation, StateOfDonation::Open, "State of donation should be open.");
+    }
+
+    #[test]
+    fn test_major_donor_event_logged_once() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.recognition_threshold = Some(Amount::from_micro_ccd(250));
+        let mut host = TestHost::new(state, state_builder);
+        let mut logger = TestLogger::init();
+
+        // act
+        for _ in 0..4 {
+            let result = donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut logger);
+            assert!(result.is_ok(), "Inserting CCD results in error");
+        }
+
+        // assert
+        let new_donation = to_bytes(&DonationEvent::NewDonation {
+            donor: ACC,
+            location: "CM".to_string(),
+            amount: Amount::from_micro_ccd(100),
+        });
+        assert_eq!(
+            logger.logs,
+            [
+                new_donation.clone(),
+                new_donation.clone(),
+                new_donation.clone(),
+                to_bytes(&DonationEvent::MajorDonor {
+                    donor: ACC,
+                    total: Amount::from_micro_ccd(300),
+                }),
+                new_donation,
+            ],
+            "MajorDonor should be logged exactly once, on crossing the threshold"
+        );
+    }
+
+    #[test]
+    fn test_set_recognition_threshold_not_owner() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(AccountAddress([1u8; 32])));
+        let parameter = to_bytes(&Some(Amount::from_micro_ccd(250)));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let result = set_recognition_threshold(&ctx, &mut host);
+
+        // assert
+        assert_eq!(result, Err(Error::Unauthorized), "Only the owner may set the threshold");
+        assert_eq!(host.state().recognition_threshold, None, "Threshold should be unchanged");
+    }
+
+    #[test]
+    fn test_view_projection() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        // 1000ms in with 9000ms remaining
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(1000));
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        // velocity of 1 microCCD per millisecond
+        state.total_donated = Amount::from_micro_ccd(1000);
+        let host = TestHost::new(state, state_builder);
+
+        // act
+        let result = view_projection(&ctx, &host);
+
+        // assert
+        assert_eq!(result, Ok(Amount::from_micro_ccd(10000)), "Wrong projection");
+    }
+
+    #[test]
+    fn test_view_projection_after_end_time() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(20000));
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.total_donated = Amount::from_micro_ccd(1000);
+        let host = TestHost::new(state, state_builder);
+
+        // act
+        let result = view_projection(&ctx, &host);
+
+        // assert
+        assert_eq!(result, Ok(Amount::from_micro_ccd(1000)), "Projection should equal the total");
+    }
+
+    #[test]
+    fn test_update_price_from_oracle() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.oracle = Some(ORACLE);
+        state.total_donated = Amount::from_ccd(10);
+        let mut host = TestHost::new(state, state_builder);
+        host.setup_mock_entrypoint(
+            ORACLE,
+            OwnedEntrypointName::new_unchecked("getPrice".to_string()),
+            MockFn::returning_ok(250u64),
+        );
+
+        // act
+        let result = update_price_from_oracle(&ctx, &mut host);
+
+        // assert
+        assert!(result.is_ok(), "Failed to update the price");
+        assert_eq!(host.state().fiat_rate, Some(250), "Rate should be taken from the oracle");
+        assert_eq!(
+            view_progress(&ctx, &host),
+            Ok(ProgressView {
+                total_donated: Amount::from_ccd(10),
+                fiat_rate: Some(250),
+                total_fiat: Some(2500),
+            }),
+            "Progress should use the stored rate"
+        );
+    }
+
+    #[test]
+    fn test_update_price_from_oracle_failure() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.oracle = Some(ORACLE);
+        state.fiat_rate = Some(100);
+        let mut host = TestHost::new(state, state_builder);
+        host.setup_mock_entrypoint(
+            ORACLE,
+            OwnedEntrypointName::new_unchecked("getPrice".to_string()),
+            MockFn::returning_err::<()>(CallContractError::Trap),
+        );
+
+        // act
+        let result = update_price_from_oracle(&ctx, &mut host);
+
+        // assert
+        assert_eq!(result, Err(Error::OracleFailed), "Oracle failure should be reported");
+        assert_eq!(host.state().fiat_rate, Some(100), "Previous rate should be kept");
+    }
+
+    #[test]
+    fn test_withdraw_split() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let beneficiary_a = AccountAddress([1u8; 32]);
+        let beneficiary_b = AccountAddress([2u8; 32]);
+        let parameter = to_bytes(&WithdrawParams {
+            amount: Amount::from_micro_ccd(100),
+            splits: vec![
+                (beneficiary_a, Amount::from_micro_ccd(60)),
+                (beneficiary_b, Amount::from_micro_ccd(40)),
+            ],
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(150));
+
+        // act
+        let result = withdraw(&ctx, &mut host);
+
+        // assert
+        assert!(result.is_ok(), "Failed to withdraw.");
+        assert_eq!(
+            host.get_transfers(),
+            [(beneficiary_a, Amount::from_micro_ccd(60)), (beneficiary_b, Amount::from_micro_ccd(40))],
+            "wrong transfers."
+        );
+    }
+
+    #[test]
+    fn test_withdraw_split_mismatch() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let parameter = to_bytes(&WithdrawParams {
+            amount: Amount::from_micro_ccd(100),
+            splits: vec![
+                (AccountAddress([1u8; 32]), Amount::from_micro_ccd(60)),
+                (AccountAddress([2u8; 32]), Amount::from_micro_ccd(60)),
+            ],
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(150));
+
+        // act
+        let result = withdraw(&ctx, &mut host);
+
+        // assert
+        assert_eq!(result, Err(Error::SplitSumMismatch), "Mismatching split should be rejected");
+        assert!(host.get_transfers().is_empty(), "No transfer should happen");
+    }
+
+    #[test]
+    fn test_donate_view_only() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.donations_enabled = false;
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(100));
+        let mut logger = TestLogger::init();
+
+        // act
+        let result = donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut logger);
+
+        // assert
+        assert_eq!(result, Err(Error::DonationsDisabled), "Donations should be rejected");
+        let view_result = view(&ctx, &host);
+        assert!(view_result.is_ok(), "View should still work");
+        let donation_view = view_result.unwrap();
+        assert_eq!(donation_view.state_donation, StateOfDonation::Open, "Wrong state of donation");
+        assert_eq!(donation_view.balance, Amount::from_micro_ccd(100), "Wrong balance");
+    }
+
+    #[test]
+    fn test_view_location_shares() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        for (location, amount) in [("GE", 100), ("CM", 200), ("IT", 400)] {
+            let result = donate_as(&mut host, ACC, location, Amount::from_micro_ccd(amount));
+            assert!(result.is_ok(), "Inserting CCD results in error");
+        }
+
+        // act
+        let result = view_location_shares(&TestReceiveContext::empty(), &host);
+
+        // assert
+        assert_eq!(
+            result,
+            Ok(vec![
+                ("GE".to_string(), 1429),
+                ("CM".to_string(), 2857),
+                ("IT".to_string(), 5714),
+                ("FR".to_string(), 0),
+            ]),
+            "Wrong location shares"
+        );
+    }
+
+    #[test]
+    fn test_view_location_shares_late_donations() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.grace_period = Duration::from_millis(100);
+        state.grace_donations_flagged = true;
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(10050));
+        let params = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&params);
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init()).is_ok());
+
+        // act
+        let result = view_location_shares(&TestReceiveContext::empty(), &host);
+
+        // assert
+        assert_eq!(
+            result,
+            Ok(vec![
+                ("GE".to_string(), 5000),
+                ("CM".to_string(), 5000),
+                ("IT".to_string(), 0),
+                ("FR".to_string(), 0),
+            ]),
+            "Late donations should be part of the shares"
+        );
+    }
+
+    #[test]
+    fn test_deferred_donation_activates() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&DonateParams {
+            location: "CM".to_string(),
+            activate_at: Some(Timestamp::from_timestamp_millis(5000)),
+            ..donate_params("CM")
+        });
+        ctx.set_parameter(&parameter);
+        let amount = Amount::from_micro_ccd(100);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        let mut logger = TestLogger::init();
+
+        // act
+        let result = donate(&ctx, &mut host, amount, &mut logger);
+
+        // assert
+        assert!(result.is_ok(), "Inserting CCD results in error");
+        assert_eq!(host.state().total_donated, Amount::zero(), "Deferred donation should not count yet");
+        assert_eq!(
+            view_pending(&ctx, &host),
+            Ok(vec![(0, PendingDonation {
+                donation: Donation {
+                    donor: ACC,
+                    location: "CM".to_string(),
+                    amount,
+                    tag: None,
+                    anonymous: false,
+                    hide_amount: false,
+                },
+                activate_at: Some(Timestamp::from_timestamp_millis(5000)),
+            })]),
+            "Deferred donation should be pending"
+        );
+
+        // too early to activate
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(4999));
+        assert!(activate_pending(&ctx, &mut host, &mut logger).is_ok(), "Failed to activate");
+        assert_eq!(host.state().total_donated, Amount::zero(), "Deferred donation should not count yet");
+
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(5000));
+        assert!(activate_pending(&ctx, &mut host, &mut logger).is_ok(), "Failed to activate");
+        assert_eq!(host.state().total_donated, amount, "Activated donation should count");
+        assert_eq!(*host.state().donations.get(&ACC).unwrap(), amount, "Wrong donor total");
+        assert_eq!(host.state().pending_total, Amount::zero(), "Nothing should be held");
+        assert_eq!(view_pending(&ctx, &host), Ok(Vec::new()), "Nothing should be pending");
+    }
+
+    #[test]
+    fn test_close_keeps_pending_donations() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.pending_total = Amount::from_micro_ccd(30);
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(100));
+
+        // act
+        let result = close(&ctx, &mut host, &mut TestLogger::init());
+
+        // assert
+        assert!(result.is_ok(), "Failed to close donation.");
+        assert_eq!(
+            host.get_transfers(),
+            [(ACC, Amount::from_micro_ccd(70))],
+            "Held CCD should not be transferred."
+        );
+    }
+
+    #[test]
+    fn test_donate_auto_close_on_cap() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.max_total = Some(Amount::from_micro_ccd(200));
+        state.auto_close_on_cap = true;
+        let mut host = TestHost::new(state, state_builder);
+        let mut logger = TestLogger::init();
+
+        // act
+        let first = donate(&ctx, &mut host, Amount::from_micro_ccd(150), &mut logger);
+        let over_cap = donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut logger);
+        let reaching_cap = donate(&ctx, &mut host, Amount::from_micro_ccd(50), &mut logger);
+
+        // assert
+        assert!(first.is_ok(), "Inserting CCD results in error");
+        assert_eq!(over_cap, Err(Error::MaxTotalExceeded), "Donation over the cap should be rejected");
+        assert!(reaching_cap.is_ok(), "Donation reaching the cap should be accepted");
+        assert_eq!(
+            host.state().state_of_donation,
+            StateOfDonation::Closed,
+            "State of donation should be closed"
+        );
+        let new_donation = |amount| {
+            to_bytes(&DonationEvent::NewDonation {
+                donor: ACC,
+                location: "CM".to_string(),
+                amount: Amount::from_micro_ccd(amount),
+            })
+        };
+        assert_eq!(
+            logger.logs,
+            [new_donation(150), new_donation(50), to_bytes(&DonationEvent::Closed)],
+            "Closed should be logged"
+        );
+        assert_eq!(
+            donate(&ctx, &mut host, Amount::from_micro_ccd(1), &mut logger),
+            Err(Error::DonationClosed),
+            "Closed campaign should reject donations"
+        );
+    }
+
+    #[test]
+    fn test_view_twa() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        let parameter = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        let mut logger = TestLogger::init();
+
+        // 100 with 10000ms left and 400 with 5000ms left
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut logger).is_ok());
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(5000));
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(400), &mut logger).is_ok());
+
+        // act
+        let result = view_twa(&ctx, &host);
+
+        // assert
+        // (100 * 10000 + 400 * 5000) / (10000 + 5000)
+        assert_eq!(result, Ok(Amount::from_micro_ccd(200)), "Wrong time-weighted average");
+    }
+
+    #[test]
+    fn test_close_location_coverage() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.min_funded_locations = 2;
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+
+        // act
+        let insufficient = close(&ctx, &mut host, &mut TestLogger::init());
+        assert!(donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(100)).is_ok());
+        let sufficient = close(&ctx, &mut host, &mut TestLogger::init());
+
+        // assert
+        assert_eq!(
+            insufficient,
+            Err(Error::InsufficientCoverage.into()),
+            "Closing with one funded location should fail"
+        );
+        assert!(sufficient.is_ok(), "Failed to close donation.");
+        assert_eq!(host.state().state_of_donation, StateOfDonation::Closed, "State of donation should be closed.");
+    }
+
+    #[test]
+    fn test_donor_cap_exemption() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let exempt = AccountAddress([1u8; 32]);
+        let not_exempt = AccountAddress([2u8; 32]);
+        let parameter = to_bytes(&exempt);
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.max_per_donor = Some(Amount::from_micro_ccd(100));
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let exemption = add_donor_cap_exemption(&ctx, &mut host);
+        let exempt_result = donate_as(&mut host, exempt, "CM", Amount::from_micro_ccd(150));
+        let within_cap = donate_as(&mut host, not_exempt, "CM", Amount::from_micro_ccd(100));
+        let over_cap = donate_as(&mut host, not_exempt, "CM", Amount::from_micro_ccd(1));
+
+        // assert
+        assert!(exemption.is_ok(), "Failed to add exemption");
+        assert!(exempt_result.is_ok(), "Exempt donor should bypass the cap");
+        assert!(within_cap.is_ok(), "Donation within the cap should be accepted");
+        assert_eq!(over_cap, Err(Error::DonorCapExceeded), "Donation over the cap should be rejected");
+    }
+
+    #[test]
+    fn test_view_explorer() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.max_total = Some(Amount::from_micro_ccd(1000));
+        state.auto_close_on_cap = true;
+        state.max_per_donor = Some(Amount::from_micro_ccd(500));
+        state.min_funded_locations = 1;
+        state.recognition_threshold = Some(Amount::from_micro_ccd(300));
+        state.oracle = Some(ORACLE);
+        state.fiat_rate = Some(250);
+        state.pending_total = Amount::from_micro_ccd(20);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(120));
+
+        // act
+        let result = view_explorer(&ctx, &host);
+
+        // assert
+        assert_eq!(
+            result,
+            Ok(ExplorerView {
+                version: EXPLORER_VIEW_VERSION,
+                config: ExplorerConfig {
+                    donation_locations: init_parameter().donation_locations,
+                    start_time: Timestamp::from_timestamp_millis(0),
+                    end_time: Timestamp::from_timestamp_millis(10000),
+                    goal: Amount::zero(),
+                    donations_enabled: true,
+                    max_total: Some(Amount::from_micro_ccd(1000)),
+                    auto_close_on_cap: true,
+                    max_per_donor: Some(Amount::from_micro_ccd(500)),
+                    min_funded_locations: 1,
+                    recognition_threshold: Some(Amount::from_micro_ccd(300)),
+                    oracle: Some(ORACLE),
+                },
+                stats: ExplorerStats {
+                    number_of_donors: 1,
+                    total_donated: Amount::from_micro_ccd(100),
+                    pending_total: Amount::from_micro_ccd(20),
+                    balance: Amount::from_micro_ccd(120),
+                    fiat_rate: Some(250),
+                    location_totals: vec![
+                        ("GE".to_string(), Amount::zero()),
+                        ("CM".to_string(), Amount::from_micro_ccd(100)),
+                        ("IT".to_string(), Amount::zero()),
+                        ("FR".to_string(), Amount::zero()),
+                    ],
+                },
+                recipient: ACC,
+                status: StateOfDonation::Open,
+            }),
+            "Wrong explorer view"
+        );
+    }
+
+    #[test]
+    fn test_donation_tags() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let memorial = to_bytes(&DonateParams {
+            tag: Some("memorial".to_string()),
+            ..donate_params("CM")
+        });
+        let corporate = to_bytes(&DonateParams {
+            tag: Some("corporate".to_string()),
+            ..donate_params("GE")
+        });
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        let mut logger = TestLogger::init();
+
+        // act
+        for (parameter, amount) in [(&memorial, 100), (&corporate, 300), (&memorial, 50)] {
+            ctx.set_parameter(parameter);
+            let result = donate(&ctx, &mut host, Amount::from_micro_ccd(amount), &mut logger);
+            assert!(result.is_ok(), "Inserting CCD results in error");
+        }
+
+        // assert
+        let tag = to_bytes(&"memorial".to_string());
+        ctx.set_parameter(&tag);
+        assert_eq!(view_by_tag(&ctx, &host), Ok(Amount::from_micro_ccd(150)), "Wrong memorial total");
+        let count = to_bytes(&1u32);
+        ctx.set_parameter(&count);
+        assert_eq!(
+            view_top_tags(&ctx, &host),
+            Ok(vec![("corporate".to_string(), Amount::from_micro_ccd(300))]),
+            "Wrong top tags"
+        );
+    }
+
+    #[test]
+    fn test_donate_tag_too_long() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&DonateParams {
+            tag: Some("x".repeat(MAX_TAG_LENGTH + 1)),
+            ..donate_params("CM")
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let result = donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init());
+
+        // assert
+        assert_eq!(result, Err(Error::InvalidString), "Over-long tag should be rejected");
+    }
+
+    #[test]
+    fn test_view_would_complete_goal() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(1000);
+        state.total_donated = Amount::from_micro_ccd(600);
+        let host = TestHost::new(state, state_builder);
+
+        // act
+        let mut ctx = TestReceiveContext::empty();
+        let completing = to_bytes(&Amount::from_micro_ccd(400));
+        ctx.set_parameter(&completing);
+        let completes = view_would_complete_goal(&ctx, &host);
+        let short = to_bytes(&Amount::from_micro_ccd(399));
+        ctx.set_parameter(&short);
+        let falls_short = view_would_complete_goal(&ctx, &host);
+
+        // assert
+        assert_eq!(completes, Ok(true), "Donation should complete the goal");
+        assert_eq!(falls_short, Ok(false), "Donation should not complete the goal");
+    }
+
+    #[test]
+    fn test_set_goal_after_lock() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let parameter = to_bytes(&Amount::from_micro_ccd(500));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let before_lock = set_goal(&ctx, &mut host);
+        let lock = lock_goal(&ctx, &mut host);
+        let new_goal = to_bytes(&Amount::from_micro_ccd(900));
+        ctx.set_parameter(&new_goal);
+        let after_lock = set_goal(&ctx, &mut host);
+
+        // assert
+        assert!(before_lock.is_ok(), "Failed to set the goal");
+        assert!(lock.is_ok(), "Failed to lock the goal");
+        assert_eq!(after_lock, Err(Error::GoalLocked), "Locked goal should not change");
+        assert_eq!(host.state().goal, Amount::from_micro_ccd(500), "Goal should be unchanged");
+    }
+
+    #[test]
+    fn test_view_recent_donations() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        let donor_a = AccountAddress([1u8; 32]);
+        let donor_b = AccountAddress([2u8; 32]);
+        let anonymous = to_bytes(&DonateParams {
+            anonymous: true,
+            ..donate_params("GE")
+        });
+        let public = to_bytes(&donate_params("CM"));
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        let mut logger = TestLogger::init();
+
+        for (donor, parameter, time) in [(donor_a, &public, 1), (donor_b, &anonymous, 2), (donor_a, &public, 3)] {
+            ctx.set_sender(Address::Account(donor));
+            ctx.set_invoker(donor);
+            ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(time));
+            ctx.set_parameter(parameter);
+            let result = donate(&ctx, &mut host, Amount::from_micro_ccd(time * 100), &mut logger);
+            assert!(result.is_ok(), "Inserting CCD results in error");
+        }
+
+        // act
+        let count = to_bytes(&2u32);
+        ctx.set_parameter(&count);
+        let result = view_recent_donations(&ctx, &host);
+
+        // assert
+        assert_eq!(
+            result,
+            Ok(vec![
+                RecentDonation {
+                    donor: Some(donor_a),
+                    location: "CM".to_string(),
+                    amount: Some(Amount::from_micro_ccd(300)),
+                    time: Timestamp::from_timestamp_millis(3),
+                },
+                RecentDonation {
+                    donor: None,
+                    location: "GE".to_string(),
+                    amount: Some(Amount::from_micro_ccd(200)),
+                    time: Timestamp::from_timestamp_millis(2),
+                },
+            ]),
+            "Wrong recent donations"
+        );
+    }
+
+    #[test]
+    fn test_donation_queued_while_closed() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&parameter);
+        let amount = Amount::from_micro_ccd(100);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.state_of_donation = StateOfDonation::Closed;
+        state.queue_while_closed = true;
+        let mut host = TestHost::new(state, state_builder);
+        let mut logger = TestLogger::init();
+
+        // act
+        let result = donate(&ctx, &mut host, amount, &mut logger);
+
+        // assert
+        assert!(result.is_ok(), "Donation should be queued");
+        assert_eq!(host.state().total_donated, Amount::zero(), "Queued donation should not count yet");
+        assert_eq!(host.state().pending_total, amount, "Queued donation should be held");
+
+        let open_result = open(&ctx, &mut host, &mut logger);
+        assert!(open_result.is_ok(), "Failed to open donation.");
+        assert_eq!(host.state().total_donated, amount, "Queued donation should count after reopening");
+        assert_eq!(host.state().pending_total, Amount::zero(), "Nothing should be held");
+    }
+
+    #[test]
+    fn test_donate_closed_without_queue() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.state_of_donation = StateOfDonation::Closed;
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let result = donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init());
+
+        // assert
+        assert_eq!(result, Err(Error::DonationClosed), "Closed campaign should reject donations");
+    }
+
+    #[test]
+    fn test_validate_str() {
+        assert_eq!(validate_str("", 4), Err(Error::InvalidString), "Empty string should be rejected");
+        assert_eq!(validate_str("abcde", 4), Err(Error::InvalidString), "Over-long string should be rejected");
+        assert_eq!(validate_str("abcd", 4), Ok(()), "String at the limit should be accepted");
+    }
+
+    #[test]
+    fn test_init_invalid_location() {
+        // arrange
+        let mut ctx = TestInitContext::empty();
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&InitParameter {
+            donation_locations: vec!["GE".to_string(), String::new()],
+            ..init_parameter()
+        });
+        ctx.set_parameter(&parameter);
+
+        // act
+        let result = init(&ctx, &mut TestStateBuilder::new(), Amount::zero());
+
+        // assert
+        assert_eq!(result.err(), Some(Error::InvalidString.into()), "Empty location should be rejected");
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        assert!(donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(100)).is_ok());
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(1000));
+        let first = snapshot_checkpoint(&ctx, &mut host);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(250)).is_ok());
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(2000));
+        let second = snapshot_checkpoint(&ctx, &mut host);
+
+        // assert
+        assert!(first.is_ok() && second.is_ok(), "Failed to take checkpoints");
+        assert_eq!(
+            view_checkpoints(&ctx, &host),
+            Ok(vec![
+                (Timestamp::from_timestamp_millis(1000), Amount::from_micro_ccd(100)),
+                (Timestamp::from_timestamp_millis(2000), Amount::from_micro_ccd(350)),
+            ]),
+            "Wrong checkpoints"
+        );
+    }
+
+    #[test]
+    fn test_view_location_exists() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let host = TestHost::new(state, state_builder);
+
+        // act
+        let mut ctx = TestReceiveContext::empty();
+        let existing = to_bytes(&"CM".to_string());
+        ctx.set_parameter(&existing);
+        let exists = view_location_exists(&ctx, &host);
+        let missing = to_bytes(&"USA".to_string());
+        ctx.set_parameter(&missing);
+        let does_not_exist = view_location_exists(&ctx, &host);
+
+        // assert
+        assert_eq!(exists, Ok(true), "CM should exist");
+        assert_eq!(does_not_exist, Ok(false), "USA should not exist");
+    }
+
+    #[test]
+    fn test_donate_fiat_minimum_follows_rate() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        // 5.00 in fiat
+        state.min_donation_fiat = Some(500);
+        // 2.50 per CCD, so the minimum is 2 CCD
+        state.fiat_rate = Some(250);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let below = donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(1_999_999));
+        let at_minimum = donate_as(&mut host, ACC, "CM", Amount::from_ccd(2));
+        // 5.00 per CCD, so the minimum is 1 CCD
+        host.state_mut().fiat_rate = Some(500);
+        let after_rate_change = donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(1_999_999));
+
+        // assert
+        assert_eq!(below, Err(Error::BelowMinimum), "Donation below the minimum should be rejected");
+        assert!(at_minimum.is_ok(), "Donation at the minimum should be accepted");
+        assert!(after_rate_change.is_ok(), "Higher rate should lower the minimum");
+    }
+
+    #[test]
+    fn test_view_stats_donor_kinds() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        let mut logger = TestLogger::init();
+
+        // act
+        ctx.set_sender(Address::Account(ACC));
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut logger).is_ok());
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut logger).is_ok());
+        ctx.set_sender(Address::Contract(ContractAddress {
+            index: 7,
+            subindex: 0,
+        }));
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut logger).is_ok());
+        let result = view_stats(&ctx, &host);
+
+        // assert
+        let stats = result.unwrap();
+        assert_eq!(stats.account_donors, 1, "One account should have donated");
+        assert_eq!(stats.contract_donors, 1, "One contract should have donated");
+        assert_eq!(stats.total_donated, Amount::from_micro_ccd(300), "Wrong total");
+    }
+
+    #[test]
+    fn test_donate_trim_to_goal() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(1000);
+        state.trim_to_goal = true;
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(1100));
+
+        // act
+        let filling = donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(600));
+        let overshooting = donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(500));
+
+        // assert
+        assert!(filling.is_ok(), "Donation within the goal should be accepted");
+        assert!(overshooting.is_ok(), "Overshooting donation should be trimmed");
+        assert_eq!(host.state().total_donated, Amount::from_micro_ccd(1000), "Total should equal the goal");
+        assert_eq!(host.get_transfers(), [(ACC, Amount::from_micro_ccd(100))], "Excess should be refunded");
+    }
+
+    #[test]
+    fn test_donate_trim_to_goal_refunds_sender() {
+        // arrange
+        let sender = AccountAddress([1u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("CM"));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(1000);
+        state.trim_to_goal = true;
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(1200));
+
+        // act
+        ctx.set_sender(Address::Contract(ORACLE));
+        let from_contract = donate(&ctx, &mut host, Amount::from_micro_ccd(1100), &mut TestLogger::init());
+        ctx.set_sender(Address::Account(sender));
+        let from_account = donate(&ctx, &mut host, Amount::from_micro_ccd(1100), &mut TestLogger::init());
+
+        // assert
+        assert_eq!(from_contract, Err(Error::ExcessFromContract), "A contract cannot take the excess back");
+        assert!(from_account.is_ok(), "Overshooting donation should be trimmed");
+        assert_eq!(host.get_transfers(), [(sender, Amount::from_micro_ccd(100))], "Excess should go to the sender");
+    }
+
+    #[test]
+    fn test_donate_trim_to_goal_exact() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(1000);
+        state.trim_to_goal = true;
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let exact = donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(1000));
+        let after_goal = donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(1));
+
+        // assert
+        assert!(exact.is_ok(), "Donation exactly filling the goal should be accepted");
+        assert!(host.get_transfers().is_empty(), "Nothing should be refunded");
+        assert_eq!(after_goal, Err(Error::GoalReached), "Donations past the goal should be rejected");
+    }
+
+    #[test]
+    fn test_view_next_milestone() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(1000);
+        let mut host = TestHost::new(state, state_builder);
+        let ctx = TestReceiveContext::empty();
+
+        // act
+        let at_start = view_next_milestone(&ctx, &host);
+        host.state_mut().total_donated = Amount::from_micro_ccd(250);
+        let at_first = view_next_milestone(&ctx, &host);
+        host.state_mut().total_donated = Amount::from_micro_ccd(900);
+        let near_goal = view_next_milestone(&ctx, &host);
+        host.state_mut().total_donated = Amount::from_micro_ccd(1000);
+        let at_goal = view_next_milestone(&ctx, &host);
+
+        // assert
+        assert_eq!(
+            at_start,
+            Ok(Some(NextMilestone {
+                milestone_bps: 2500,
+                amount: Amount::from_micro_ccd(250),
+                gap: Amount::from_micro_ccd(250),
+            })),
+            "Wrong milestone at start"
+        );
+        assert_eq!(
+            at_first,
+            Ok(Some(NextMilestone {
+                milestone_bps: 5000,
+                amount: Amount::from_micro_ccd(500),
+                gap: Amount::from_micro_ccd(250),
+            })),
+            "Reached milestone should be skipped"
+        );
+        assert_eq!(
+            near_goal,
+            Ok(Some(NextMilestone {
+                milestone_bps: 10000,
+                amount: Amount::from_micro_ccd(1000),
+                gap: Amount::from_micro_ccd(100),
+            })),
+            "Wrong milestone near the goal"
+        );
+        assert_eq!(at_goal, Ok(None), "All milestones should be reached");
+    }
+
+    #[test]
+    fn test_location_goals() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let param = InitParameter {
+            location_goals: vec![
+                ("GE".to_string(), Amount::from_micro_ccd(100)),
+                ("CM".to_string(), Amount::from_micro_ccd(500)),
+            ],
+            ..init_parameter()
+        };
+        let state = State::new(param, ACC, Timestamp::from_timestamp_millis(0), &mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        assert!(donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(200)).is_ok());
+
+        // act
+        let mut ctx = TestReceiveContext::empty();
+        let all_locations = view_all_locations(&ctx, &host);
+        let parameter = to_bytes(&"CM".to_string());
+        ctx.set_parameter(&parameter);
+        let location = view_location(&ctx, &host);
+
+        // assert
+        let cm = LocationView {
+            location: "CM".to_string(),
+            total: Amount::from_micro_ccd(200),
+            donors: 1,
+            goal: Some(Amount::from_micro_ccd(500)),
+            goal_reached: false,
+        };
+        assert_eq!(location, Ok(cm), "Wrong CM location view");
+        let all_locations = all_locations.unwrap();
+        assert_eq!(
+            all_locations[0],
+            LocationView {
+                location: "GE".to_string(),
+                total: Amount::from_micro_ccd(100),
+                donors: 1,
+                goal: Some(Amount::from_micro_ccd(100)),
+                goal_reached: true,
+            },
+            "GE should have reached its goal"
+        );
+        assert!(!all_locations[1].goal_reached, "CM should not have reached its goal");
+        assert_eq!(all_locations[2].goal, None, "IT should have no goal");
+    }
+
+    #[test]
+    fn test_set_location_goals() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let parameter = to_bytes(&vec![
+            ("GE".to_string(), Amount::from_micro_ccd(100)),
+            ("CM".to_string(), Amount::from_micro_ccd(200)),
+        ]);
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let result = set_location_goals(&ctx, &mut host);
+
+        // assert
+        assert!(result.is_ok(), "Failed to set location goals");
+        assert_eq!(host.state().location_view(&"GE".to_string()).goal, Some(Amount::from_micro_ccd(100)));
+        assert_eq!(host.state().location_view(&"CM".to_string()).goal, Some(Amount::from_micro_ccd(200)));
+    }
+
+    #[test]
+    fn test_set_location_goals_unknown_location() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let parameter = to_bytes(&vec![
+            ("GE".to_string(), Amount::from_micro_ccd(100)),
+            ("USA".to_string(), Amount::from_micro_ccd(200)),
+        ]);
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let result = set_location_goals(&ctx, &mut host);
+
+        // assert
+        assert_eq!(result, Err(Error::InvalidDonationLocation), "Unknown location should be rejected");
+        assert_eq!(host.state().location_view(&"GE".to_string()).goal, None, "No goal should be applied");
+    }
+
+    #[test]
+    fn test_view_check_invariants() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(100)).is_ok());
+        assert!(donate_as(&mut host, AccountAddress([1u8; 32]), "GE", Amount::from_micro_ccd(50)).is_ok());
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(25)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(175));
+        let ctx = TestReceiveContext::empty();
+
+        // act
+        let consistent = view_check_invariants(&ctx, &host);
+        host.state_mut().total_donated = Amount::from_micro_ccd(200);
+        let corrupted = view_check_invariants(&ctx, &host);
+
+        // assert
+        assert_eq!(
+            consistent,
+            Ok(InvariantsView {
+                donor_count: true,
+                location_totals: true,
+                balance: true,
+                consistent: true,
+            }),
+            "State should be consistent"
+        );
+        assert_eq!(
+            corrupted,
+            Ok(InvariantsView {
+                donor_count: true,
+                location_totals: false,
+                balance: false,
+                consistent: false,
+            }),
+            "Corrupted total should be detected"
+        );
+    }
+
+    #[test]
+    fn test_hide_amount() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let hidden_donor = AccountAddress([1u8; 32]);
+        let hidden = to_bytes(&DonateParams {
+            hide_amount: true,
+            ..donate_params("CM")
+        });
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        ctx.set_sender(Address::Account(hidden_donor));
+        ctx.set_invoker(hidden_donor);
+        ctx.set_parameter(&hidden);
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(500), &mut TestLogger::init()).is_ok());
+
+        // act
+        let count = to_bytes(&10u32);
+        ctx.set_parameter(&count);
+        let leaderboard = view_leaderboard(&ctx, &host);
+        let recent = view_recent_donations(&ctx, &host).unwrap();
+
+        // assert
+        assert_eq!(host.state().number_of_donors, 2, "Hidden donor should be counted");
+        assert_eq!(
+            leaderboard,
+            Ok(vec![
+                LeaderboardEntry {
+                    donor: Some(hidden_donor),
+                    total: None,
+                },
+                LeaderboardEntry {
+                    donor: Some(ACC),
+                    total: Some(Amount::from_micro_ccd(100)),
+                },
+            ]),
+            "Hidden donor should appear without an amount"
+        );
+        assert_eq!(recent[0].donor, Some(hidden_donor), "Hidden donor should appear in the feed");
+        assert_eq!(recent[0].amount, None, "Hidden amount should be redacted");
+    }
+
+    #[test]
+    fn test_scheduled_pause() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        let params = to_bytes(&donate_params("GE"));
+        ctx.set_parameter(&params);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.scheduled_pause_at = Some(Timestamp::from_timestamp_millis(100));
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(99));
+        let before = donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init());
+        let status_before = view_status(&ctx, &host);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(100));
+        let after = donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init());
+        let status_after = view_status(&ctx, &host);
+
+        // assert
+        assert!(before.is_ok(), "Donations before the pause should be accepted");
+        assert_eq!(status_before, Ok(CampaignStatus::Open), "Campaign should be open before the pause");
+        assert_eq!(after, Err(Error::DonationPaused), "Donations after the pause should be rejected");
+        assert_eq!(status_after, Ok(CampaignStatus::Paused), "Campaign should be paused");
+        assert_eq!(host.state().total_donated, Amount::from_micro_ccd(100), "Only the first donation should count");
+    }
+
+    #[test]
+    fn test_view_event_schema() {
+        // arrange
+        let ctx = TestReceiveContext::empty();
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let host = TestHost::new(state, state_builder);
+        // adding an event variant fails to compile here until it is listed
+        let variant_name = |event: &DonationEvent| match event {
+            DonationEvent::MajorDonor {
+                ..
+            } => "MajorDonor",
+            DonationEvent::Closed => "Closed",
+            DonationEvent::NewDonation {
+                ..
+            } => "NewDonation",
+            DonationEvent::Opened => "Opened",
+        };
+        let events = [
+            DonationEvent::MajorDonor {
+                donor: ACC,
+                total: Amount::zero(),
+            },
+            DonationEvent::Closed,
+            DonationEvent::NewDonation {
+                donor: ACC,
+                location: "GE".to_string(),
+                amount: Amount::zero(),
+            },
+            DonationEvent::Opened,
+        ];
+
+        // act
+        let schema = view_event_schema(&ctx, &host).unwrap();
+
+        // assert
+        let names: Vec<&str> = schema.iter().map(|variant| variant.name.as_str()).collect();
+        let expected: Vec<&str> = events.iter().map(variant_name).collect();
+        assert_eq!(names, expected, "Every event variant should be described");
+        assert_eq!(
+            schema[0].fields,
+            vec![
+                EventFieldSchema {
+                    name: "donor".to_string(),
+                    type_name: "AccountAddress".to_string(),
+                },
+                EventFieldSchema {
+                    name: "total".to_string(),
+                    type_name: "Amount".to_string(),
+                },
+            ],
+            "Fields should be described in order"
+        );
+    }
+
+    #[test]
+    fn test_grace_donations() {
+        for flagged in [false, true] {
+            // arrange
+            let mut ctx = TestReceiveContext::empty();
+            ctx.set_sender(Address::Account(ACC));
+            ctx.set_invoker(ACC);
+            let params = to_bytes(&donate_params("GE"));
+            ctx.set_parameter(&params);
+
+            let mut state_builder = TestStateBuilder::new();
+            let mut state = test_state(&mut state_builder);
+            state.grace_period = Duration::from_millis(100);
+            state.grace_donations_flagged = flagged;
+            let mut host = TestHost::new(state, state_builder);
+
+            // act
+            ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(10050));
+            let in_grace = donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init());
+            ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(10101));
+            let after_grace = donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init());
+
+            // assert
+            assert!(in_grace.is_ok(), "Donations in the grace period should be accepted");
+            assert_eq!(after_grace, Err(Error::DonationHasEnded), "Donations after the grace period should be rejected");
+            let (counted, late) = if flagged {
+                (Amount::zero(), Amount::from_micro_ccd(100))
+            } else {
+                (Amount::from_micro_ccd(100), Amount::zero())
+            };
+            assert_eq!(host.state().total_donated, counted, "Only unflagged grace donations should count");
+            assert_eq!(host.state().late_total, late, "Only flagged grace donations should be late");
+        }
+    }
+
+    #[test]
+    fn test_withdraw_threshold() {
+        // arrange
+        let co_owner = AccountAddress([1u8; 32]);
+        let beneficiary = AccountAddress([2u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        let parameter = to_bytes(&WithdrawParams {
+            amount: Amount::from_micro_ccd(100),
+            splits: vec![(beneficiary, Amount::from_micro_ccd(100))],
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let param = InitParameter {
+            co_owners: vec![co_owner],
+            approval_threshold: 2,
+            ..init_parameter()
+        };
+        let state = State::new(param, ACC, Timestamp::from_timestamp_millis(0), &mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(150));
+
+        // act
+        ctx.set_sender(Address::Account(ACC));
+        let first = withdraw(&ctx, &mut host);
+        let transfers_after_first = host.get_transfers().len();
+        let repeated = withdraw(&ctx, &mut host);
+        let transfers_after_repeated = host.get_transfers().len();
+        ctx.set_sender(Address::Account(co_owner));
+        let second = withdraw(&ctx, &mut host);
+
+        // assert
+        assert!(first.is_ok() && repeated.is_ok() && second.is_ok(), "Approvals should be accepted");
+        assert_eq!(transfers_after_first, 0, "A single approval should not transfer");
+        assert_eq!(transfers_after_repeated, 0, "Approving twice should count once");
+        assert_eq!(
+            host.get_transfers(),
+            [(beneficiary, Amount::from_micro_ccd(100))],
+            "The threshold's worth of approvals should transfer"
+        );
+        assert_eq!(host.state().proposal, None, "The executed proposal should be cleared");
+    }
+
+    #[test]
+    fn test_init_threshold_distinct_owners() {
+        // arrange
+        let co_owner = AccountAddress([1u8; 32]);
+        let mut init_ctx = TestInitContext::empty();
+        init_ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        init_ctx.set_init_origin(ACC);
+        let duplicated = to_bytes(&InitParameter {
+            co_owners: vec![co_owner, co_owner, ACC],
+            approval_threshold: 3,
+            ..init_parameter()
+        });
+        let distinct = to_bytes(&InitParameter {
+            co_owners: vec![co_owner, co_owner, ACC],
+            approval_threshold: 2,
+            ..init_parameter()
+        });
+        let mut state_builder = TestStateBuilder::new();
+
+        // act
+        init_ctx.set_parameter(&duplicated);
+        let too_high = init(&init_ctx, &mut state_builder, Amount::zero());
+        init_ctx.set_parameter(&distinct);
+        let within = init(&init_ctx, &mut state_builder, Amount::zero());
+
+        // assert
+        assert_eq!(too_high.err(), Some(Error::InvalidThreshold.into()), "Duplicates should not count as owners");
+        assert!(within.is_ok(), "Failed to init with a threshold of the distinct owners.");
+    }
+
+    #[test]
+    fn test_close_threshold() {
+        // arrange
+        let co_owner = AccountAddress([1u8; 32]);
+        let outsider = AccountAddress([2u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+
+        let mut state_builder = TestStateBuilder::new();
+        let param = InitParameter {
+            co_owners: vec![co_owner],
+            approval_threshold: 2,
+            ..init_parameter()
+        };
+        let state = State::new(param, ACC, Timestamp::from_timestamp_millis(0), &mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(100));
+
+        // act
+        ctx.set_sender(Address::Account(outsider));
+        let unauthorized = close(&ctx, &mut host, &mut TestLogger::init());
+        ctx.set_sender(Address::Account(co_owner));
+        let first = close(&ctx, &mut host, &mut TestLogger::init());
+        let status_after_first = host.state().state_of_donation;
+        ctx.set_sender(Address::Account(ACC));
+        let second = close(&ctx, &mut host, &mut TestLogger::init());
+
+        // assert
+        assert!(unauthorized.is_err(), "Non-owners should not approve");
+        assert!(first.is_ok() && second.is_ok(), "Approvals should be accepted");
+        assert_eq!(status_after_first, StateOfDonation::Open, "A single approval should not close");
+        assert_eq!(host.state().state_of_donation, StateOfDonation::Closed, "State of donation should be closed.");
+        assert_eq!(host.get_transfers(), [(ACC, Amount::from_micro_ccd(100))], "wrong transfers.");
+    }
+
+    #[test]
+    fn test_view_can() {
+        // arrange
+        let co_owner = AccountAddress([1u8; 32]);
+        let outsider = AccountAddress([2u8; 32]);
+
+        let mut state_builder = TestStateBuilder::new();
+        let param = InitParameter {
+            co_owners: vec![co_owner],
+            ..init_parameter()
+        };
+        let state = State::new(param, ACC, Timestamp::from_timestamp_millis(0), &mut state_builder);
+        let host = TestHost::new(state, state_builder);
+        let can = |account, action| {
+            let parameter = to_bytes(&CanParams {
+                account,
+                action,
+            });
+            let mut ctx = TestReceiveContext::empty();
+            ctx.set_owner(ACC);
+            ctx.set_parameter(&parameter);
+            view_can(&ctx, &host).unwrap()
+        };
+
+        // act
+        let owner_close = can(ACC, AdminAction::Close);
+        let owner_open = can(ACC, AdminAction::Open);
+        let owner_add_location = can(ACC, AdminAction::AddLocation);
+        let co_owner_withdraw = can(co_owner, AdminAction::Withdraw);
+        let co_owner_open = can(co_owner, AdminAction::Open);
+        let outsider_close = can(outsider, AdminAction::Close);
+        let outsider_withdraw = can(outsider, AdminAction::Withdraw);
+
+        // assert
+        assert!(owner_close && owner_open, "The owner should be able to close and open");
+        assert!(!owner_add_location, "Locations cannot be added");
+        assert!(co_owner_withdraw, "Co-owners should be able to withdraw");
+        assert!(!co_owner_open, "Only the contract owner should be able to open");
+        assert!(!outsider_close && !outsider_withdraw, "Non-owners should not be authorized");
+    }
+
+    #[test]
+    fn test_deadline_extension() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.extension_threshold = Some(Amount::from_micro_ccd(1000));
+        state.extension_increment = Duration::from_millis(300);
+        state.max_extension = Duration::from_millis(500);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(1000)).is_ok());
+        let after_small = host.state().end_time;
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(1001)).is_ok());
+        let after_large = host.state().end_time;
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(5000)).is_ok());
+        let after_cap = host.state().end_time;
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(5000)).is_ok());
+
+        // assert
+        assert_eq!(after_small, Timestamp::from_timestamp_millis(10000), "Donations at the threshold should not extend");
+        assert_eq!(after_large, Timestamp::from_timestamp_millis(10300), "Large donations should extend the deadline");
+        assert_eq!(after_cap, Timestamp::from_timestamp_millis(10500), "Extensions should stop at the cap");
+        assert_eq!(host.state().end_time, after_cap, "No extension should be granted past the cap");
+    }
+
+    #[test]
+    fn test_deadline_extension_held_donation() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&DonateParams {
+            activate_at: Some(Timestamp::from_timestamp_millis(5000)),
+            ..donate_params("GE")
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.extension_threshold = Some(Amount::from_micro_ccd(500));
+        state.extension_increment = Duration::from_millis(300);
+        state.max_extension = Duration::from_millis(500);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(1000), &mut TestLogger::init()).is_ok());
+        let while_held = host.state().end_time;
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(5000));
+        assert!(activate_pending(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+
+        // assert
+        assert_eq!(while_held, Timestamp::from_timestamp_millis(10000), "Held donations should not extend");
+        assert_eq!(
+            host.state().end_time,
+            Timestamp::from_timestamp_millis(10300),
+            "The donation should extend the deadline once counted"
+        );
+    }
+
+    #[test]
+    fn test_view_activity() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(100));
+
+        // act
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        let after_donate = view_activity(&ctx, &host);
+        assert!(close(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+        let after_close = view_activity(&ctx, &host);
+        assert!(open(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+        let after_open = view_activity(&ctx, &host);
+
+        // assert
+        assert_eq!(after_donate, Ok(1), "Donating should be counted");
+        assert_eq!(after_close, Ok(2), "Closing should be counted");
+        assert_eq!(after_open, Ok(3), "Opening should be counted");
+    }
+
+    #[test]
+    fn test_view_net_position() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&DonateParams {
+            activate_at: Some(Timestamp::from_timestamp_millis(5000)),
+            ..donate_params("CM")
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init()).is_ok());
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(200)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(300));
+
+        // act
+        let position = view_net_position(&ctx, &host);
+
+        // assert
+        assert_eq!(
+            position,
+            Ok(NetPosition {
+                balance: Amount::from_micro_ccd(300),
+                held: Amount::from_micro_ccd(100),
+                match_pool: Amount::zero(),
+                owed_refunds: Amount::zero(),
+                rebate_reserved: Amount::zero(),
+                net: Amount::from_micro_ccd(200),
+            }),
+            "Held donations should be excluded from the net position"
+        );
+    }
+
+    #[test]
+    fn test_view_net_position_obligations() {
+        // arrange
+        let donor_a = AccountAddress([1u8; 32]);
+        let donor_b = AccountAddress([2u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let batch = to_bytes(&1u32);
+        ctx.set_parameter(&batch);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(200);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, donor_a, "GE", Amount::from_micro_ccd(300)).is_ok());
+        assert!(donate_as(&mut host, donor_b, "CM", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(400));
+
+        let mut refund_builder = TestStateBuilder::new();
+        let mut refunding = test_state(&mut refund_builder);
+        refunding.goal = Amount::from_micro_ccd(1000);
+        refunding.seed_balance = Amount::from_micro_ccd(50);
+        let mut refund_host = TestHost::new(refunding, refund_builder);
+        assert!(donate_as(&mut refund_host, donor_a, "GE", Amount::from_micro_ccd(100)).is_ok());
+        refund_host.set_self_balance(Amount::from_micro_ccd(150));
+        assert!(close(&ctx, &mut refund_host, &mut TestLogger::init()).is_ok());
+
+        // act
+        assert!(rebate_surplus(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+        let mid_rebate = view_net_position(&ctx, &host);
+        let while_refunding = view_net_position(&ctx, &refund_host);
+
+        // assert
+        assert_eq!(
+            mid_rebate,
+            Ok(NetPosition {
+                balance: Amount::from_micro_ccd(50),
+                held: Amount::zero(),
+                match_pool: Amount::zero(),
+                owed_refunds: Amount::zero(),
+                rebate_reserved: Amount::from_micro_ccd(50),
+                net: Amount::zero(),
+            }),
+            "The unpaid surplus should be an obligation"
+        );
+        assert_eq!(
+            while_refunding,
+            Ok(NetPosition {
+                balance: Amount::from_micro_ccd(150),
+                held: Amount::zero(),
+                match_pool: Amount::zero(),
+                owed_refunds: Amount::from_micro_ccd(100),
+                rebate_reserved: Amount::zero(),
+                net: Amount::from_micro_ccd(50),
+            }),
+            "Refunds still owed should be an obligation"
+        );
+    }
+
+    #[test]
+    fn test_view_pending_ordering() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&DonateParams {
+            activate_at: Some(Timestamp::from_timestamp_millis(5000)),
+            ..donate_params("CM")
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        // ids 255 and 256 serialize to bytes that sort the other way round
+        state.next_pending_id = 255;
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init()).is_ok());
+        let first = view_pending(&ctx, &host).unwrap();
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(50)).is_ok());
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(200), &mut TestLogger::init()).is_ok());
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(300), &mut TestLogger::init()).is_ok());
+        let second = view_pending(&ctx, &host).unwrap();
+        let third = view_pending(&ctx, &host).unwrap();
+
+        // assert
+        let ids = |pending: &[(u64, PendingDonation)]| pending.iter().map(|(id, _)| *id).collect::<Vec<u64>>();
+        assert_eq!(ids(&first), vec![255], "Pending donation should be listed");
+        assert_eq!(ids(&second), vec![255, 256, 257], "Pending donations should be ordered by id");
+        assert_eq!(second, third, "Repeated calls should return the same order");
+    }
+
+    #[test]
+    fn test_self_match() {
+        // arrange
+        let ctx = TestReceiveContext::empty();
+        let donor = AccountAddress([1u8; 32]);
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.self_match_bps = 1000;
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(1000)).is_ok());
+        let pool_after_first = host.state().match_pool;
+        let total_after_first = host.state().total_donated;
+        assert!(donate_as(&mut host, donor, "CM", Amount::from_micro_ccd(500)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(1500));
+        let invariants = view_check_invariants(&ctx, &host).unwrap();
+
+        // assert
+        assert_eq!(pool_after_first, Amount::from_micro_ccd(100), "A tenth should be diverted into the pool");
+        assert_eq!(total_after_first, Amount::from_micro_ccd(900), "The diverted part should not count yet");
+        assert_eq!(host.state().match_pool, Amount::from_micro_ccd(50), "The pool should match and be refilled");
+        assert_eq!(
+            host.state().location_totals.get(&"CM".to_string()).map(|total| *total),
+            Some(Amount::from_micro_ccd(550)),
+            "The later donation should be matched from the pool"
+        );
+        assert_eq!(
+            host.state().donations.get(&donor).map(|total| *total),
+            Some(Amount::from_micro_ccd(500)),
+            "The donor should be credited with what they gave"
+        );
+        assert!(invariants.consistent, "The pool should be accounted for in the balance");
+    }
+
+    #[test]
+    fn test_close_releases_match_pool() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.self_match_bps = 1000;
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(1000)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(1000));
+
+        // act
+        let closed = close(&ctx, &mut host, &mut TestLogger::init());
+        let invariants = view_check_invariants(&ctx, &host).unwrap();
+
+        // assert
+        assert!(closed.is_ok(), "Failed to close donation.");
+        assert_eq!(host.state().match_pool, Amount::zero(), "The pool should be released");
+        assert_eq!(host.get_transfers(), [(ACC, Amount::from_micro_ccd(1000))], "The pool should go to the owner");
+        assert!(invariants.consistent, "The released pool should be accounted for in the balance");
+    }
+
+    #[test]
+    fn test_self_match_within_cap() {
+        for auto_close_on_cap in [false, true] {
+            // arrange
+            let mut state_builder = TestStateBuilder::new();
+            let mut state = test_state(&mut state_builder);
+            state.self_match_bps = 1000;
+            state.max_total = Some(Amount::from_micro_ccd(1000));
+            state.auto_close_on_cap = auto_close_on_cap;
+            let mut host = TestHost::new(state, state_builder);
+
+            // act
+            let first = donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(1000));
+            let second = donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100));
+
+            // assert
+            assert!(first.is_ok(), "A donation up to the cap should be accepted");
+            if auto_close_on_cap {
+                assert_eq!(
+                    host.state().state_of_donation,
+                    StateOfDonation::Closed,
+                    "The pool should count towards closing on the cap"
+                );
+                assert_eq!(second, Err(Error::DonationClosed), "The closed campaign should reject donations");
+            } else {
+                assert_eq!(second, Err(Error::MaxTotalExceeded), "The pool should count towards the cap");
+            }
+            assert_eq!(host.state().received_with_pool(), Amount::from_micro_ccd(1000), "Nothing past the cap");
+        }
+    }
+
+    #[test]
+    fn test_view_donor_rank() {
+        // arrange
+        let donor_a = AccountAddress([1u8; 32]);
+        let donor_b = AccountAddress([2u8; 32]);
+        let outsider = AccountAddress([3u8; 32]);
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        assert!(donate_as(&mut host, donor_b, "GE", Amount::from_micro_ccd(300)).is_ok());
+        assert!(donate_as(&mut host, donor_a, "CM", Amount::from_micro_ccd(300)).is_ok());
+        let rank = |donor: AccountAddress| {
+            let parameter = to_bytes(&donor);
+            let mut ctx = TestReceiveContext::empty();
+            ctx.set_parameter(&parameter);
+            view_donor_rank(&ctx, &host)
+        };
+
+        // act
+        let ranks = [rank(donor_a), rank(donor_b), rank(ACC), rank(outsider)];
+
+        // assert
+        assert_eq!(ranks[0], Ok(Some(1)), "Ties should be ordered by address");
+        assert_eq!(ranks[1], Ok(Some(2)), "Ties should be ordered by address");
+        assert_eq!(ranks[2], Ok(Some(3)), "The smallest total should rank last");
+        assert_eq!(ranks[3], Ok(None), "Non-donors should have no rank");
+    }
+
+    #[test]
+    fn test_rebate_surplus() {
+        // arrange
+        let donor_a = AccountAddress([1u8; 32]);
+        let donor_b = AccountAddress([2u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let batch = to_bytes(&1u32);
+        ctx.set_parameter(&batch);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(200);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, donor_a, "GE", Amount::from_micro_ccd(300)).is_ok());
+        assert!(donate_as(&mut host, donor_b, "CM", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(400));
+
+        // act
+        let first = rebate_surplus(&ctx, &mut host, &mut TestLogger::init());
+        let transfers_after_first = host.get_transfers();
+        let second = rebate_surplus(&ctx, &mut host, &mut TestLogger::init());
+        let third = rebate_surplus(&ctx, &mut host, &mut TestLogger::init());
+
+        // assert
+        assert!(first.is_ok() && second.is_ok() && third.is_ok(), "Failed to rebate the surplus.");
+        assert_eq!(host.state().state_of_donation, StateOfDonation::Closed, "State of donation should be closed.");
+        assert_eq!(
+            transfers_after_first,
+            [(ACC, Amount::from_micro_ccd(200)), (donor_a, Amount::from_micro_ccd(150))],
+            "The goal should go to the owner and the first batch should be rebated"
+        );
+        assert_eq!(
+            host.get_transfers(),
+            [
+                (ACC, Amount::from_micro_ccd(200)),
+                (donor_a, Amount::from_micro_ccd(150)),
+                (donor_b, Amount::from_micro_ccd(50)),
+            ],
+            "Donors should be rebated in proportion to their totals, once"
+        );
+    }
+
+    #[test]
+    fn test_rebate_in_progress() {
+        // arrange
+        let donor_a = AccountAddress([1u8; 32]);
+        let donor_b = AccountAddress([2u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let batch = to_bytes(&1u32);
+        let withdrawal = to_bytes(&WithdrawParams {
+            amount: Amount::from_micro_ccd(50),
+            splits: vec![(ACC, Amount::from_micro_ccd(50))],
+        });
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(200);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, donor_a, "GE", Amount::from_micro_ccd(300)).is_ok());
+        assert!(donate_as(&mut host, donor_b, "CM", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(400));
+
+        // act
+        ctx.set_parameter(&batch);
+        assert!(rebate_surplus(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+        let available = host.state().available_balance(host.self_balance());
+        ctx.set_parameter(&withdrawal);
+        let withdrawn = withdraw(&ctx, &mut host);
+        let opened = open(&ctx, &mut host, &mut TestLogger::init());
+        let finalized = finalize_forever(&ctx, &mut host);
+        ctx.set_parameter(&batch);
+        assert!(rebate_surplus(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+        let opened_after = open(&ctx, &mut host, &mut TestLogger::init());
 
         // assert
-        assert!(result.is_ok(), "Failed to close donation.");
-        assert_eq!(host.state().state_of_donation, StateOfDonation::Closed, "State of donation should be closed.");
+        assert_eq!(available, Amount::zero(), "The rest of the surplus should be reserved");
+        assert_eq!(withdrawn, Err(Error::RebateInProgress), "Withdrawing should wait for the rebate");
+        assert_eq!(opened, Err(Error::RebateInProgress.into()), "Opening should wait for the rebate");
+        assert_eq!(finalized, Err(Error::RebateInProgress), "Finalizing should wait for the rebate");
+        assert!(opened_after.is_ok(), "Failed to open after the rebate.");
+        assert_eq!(host.state().rebate, None, "The finished rebate should be cleared");
         assert_eq!(
             host.get_transfers(),
-            [(owner, balance)],
-            "wrong transfers."
+            [
+                (ACC, Amount::from_micro_ccd(200)),
+                (donor_a, Amount::from_micro_ccd(150)),
+                (donor_b, Amount::from_micro_ccd(50)),
+            ],
+            "The whole surplus should reach the donors"
         );
     }
 
     #[test]
-    fn test_open() {
+    fn test_rebate_holds_deferred_donations() {
         // arrange
+        let donor_a = AccountAddress([1u8; 32]);
+        let donor_b = AccountAddress([2u8; 32]);
+        let donor_c = AccountAddress([3u8; 32]);
         let mut ctx = TestReceiveContext::empty();
-        let owner = AccountAddress([0u8; 32]);
-        ctx.set_owner(owner);
-        let sender = Address::Account(owner);
-        ctx.set_sender(sender);
-        let balance = Amount::from_micro_ccd(100);
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let batch = to_bytes(&1u32);
+        ctx.set_parameter(&batch);
 
-        let state = State {
-            number_of_donors: 0,
-            state_of_donation: StateOfDonation::Open,
-            donation_locations: vec!["GE".to_string(), "CM".to_string(), "IT".to_string(), "FR".to_string()],
-            end_time: Timestamp::from_timestamp_millis(10000),
-        };
-        
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(200);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, donor_a, "GE", Amount::from_micro_ccd(200)).is_ok());
+        assert!(donate_as(&mut host, donor_c, "GE", Amount::from_micro_ccd(200)).is_ok());
+        let mut deferred_ctx = TestReceiveContext::empty();
+        deferred_ctx.set_sender(Address::Account(donor_b));
+        deferred_ctx.set_invoker(donor_b);
+        deferred_ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let deferred = to_bytes(&DonateParams {
+            activate_at: Some(Timestamp::from_timestamp_millis(5000)),
+            ..donate_params("CM")
+        });
+        deferred_ctx.set_parameter(&deferred);
+        assert!(donate(&deferred_ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init()).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(500));
 
-        let mut host = TestHost::new(state, TestStateBuilder::new());
-        host.set_self_balance(balance);
         // act
-        let result = close(&ctx, &mut host);
+        assert!(rebate_surplus(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+        deferred_ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(5000));
+        let activated = activate_pending(&deferred_ctx, &mut host, &mut TestLogger::init());
+        assert!(rebate_surplus(&ctx, &mut host, &mut TestLogger::init()).is_ok());
 
         // assert
-        assert!(result.is_ok(), "Failed to close donation.");
-        assert_eq!(host.state().state_of_donation, StateOfDonation::Closed, "State of donation should be closed.");
+        assert_eq!(activated, Err(Error::RebateInProgress), "Activating should wait for the rebate");
+        assert_eq!(host.state().pending_total, Amount::from_micro_ccd(100), "The deferred donation should stay held");
         assert_eq!(
             host.get_transfers(),
-            [(owner, balance)],
+            [
+                (ACC, Amount::from_micro_ccd(200)),
+                (donor_a, Amount::from_micro_ccd(100)),
+                (donor_c, Amount::from_micro_ccd(100)),
+            ],
+            "Only the donors who funded the surplus should be rebated"
+        );
+    }
+
+    #[test]
+    fn test_rebate_sweeps_dust() {
+        // arrange
+        let donors = [AccountAddress([1u8; 32]), AccountAddress([2u8; 32]), AccountAddress([3u8; 32])];
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let batch = to_bytes(&2u32);
+        ctx.set_parameter(&batch);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(100);
+        state.dust_threshold = Amount::from_micro_ccd(10);
+        let mut host = TestHost::new(state, state_builder);
+        for donor in donors {
+            assert!(donate_as(&mut host, donor, "GE", Amount::from_micro_ccd(100)).is_ok());
+        }
+        host.set_self_balance(Amount::from_micro_ccd(300));
+
+        // act
+        assert!(rebate_surplus(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+        let dust_mid_rebate = view_dust(&ctx, &host);
+        assert!(rebate_surplus(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+        let dust_after = view_dust(&ctx, &host);
+
+        // assert
+        assert_eq!(dust_mid_rebate, Ok(Amount::zero()), "The unpaid surplus should not be dust");
+        assert_eq!(dust_after, Ok(Amount::zero()), "The dust should have been swept");
+        assert_eq!(
+            host.get_transfers(),
+            [
+                (ACC, Amount::from_micro_ccd(100)),
+                (donors[0], Amount::from_micro_ccd(66)),
+                (donors[1], Amount::from_micro_ccd(66)),
+                (donors[2], Amount::from_micro_ccd(66)),
+                (ACC, Amount::from_micro_ccd(2)),
+            ],
+            "The rounding dust should be swept to the owner"
+        );
+    }
+
+    #[test]
+    fn test_view_dust() {
+        // arrange
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.state_of_donation = StateOfDonation::Closed;
+        state.dust_threshold = Amount::from_micro_ccd(10);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        host.set_self_balance(Amount::from_micro_ccd(9));
+        let below = view_dust(&TestReceiveContext::empty(), &host);
+        host.set_self_balance(Amount::from_micro_ccd(10));
+        let at_threshold = view_dust(&TestReceiveContext::empty(), &host);
+
+        // assert
+        assert_eq!(below, Ok(Amount::from_micro_ccd(9)), "A remainder below the threshold should be dust");
+        assert_eq!(at_threshold, Ok(Amount::zero()), "A remainder at the threshold should not be dust");
+    }
+
+    #[test]
+    fn test_seed_amount() {
+        // arrange
+        let mut init_ctx = TestInitContext::empty();
+        init_ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        init_ctx.set_init_origin(ACC);
+        let parameter = to_bytes(&InitParameter {
+            seed_amount: Amount::from_micro_ccd(500),
+            ..init_parameter()
+        });
+        init_ctx.set_parameter(&parameter);
+        let mut state_builder = TestStateBuilder::new();
+        let mismatch = init(&init_ctx, &mut state_builder, Amount::from_micro_ccd(400));
+        let state = init(&init_ctx, &mut state_builder, Amount::from_micro_ccd(500)).unwrap();
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(600));
+        let invariants = view_check_invariants(&TestReceiveContext::empty(), &host).unwrap();
+
+        // assert
+        assert_eq!(mismatch.err(), Some(Error::SeedMismatch.into()), "The seed must match the amount sent");
+        assert_eq!(host.state().seed_balance, Amount::from_micro_ccd(500), "The seed should be recorded");
+        assert_eq!(host.state().total_donated, Amount::from_micro_ccd(100), "The seed should not count as donated");
+        assert_eq!(host.state().number_of_donors, 1, "The seed should not count as a donor");
+        assert!(invariants.consistent, "The seed should reconcile with the balance");
+    }
+
+    #[test]
+    fn test_view_effective_deadline() {
+        // arrange
+        let ctx = TestReceiveContext::empty();
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.grace_period = Duration::from_millis(250);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        let with_grace = view_effective_deadline(&ctx, &host);
+        host.state_mut().end_time = Timestamp::from_timestamp_millis(u64::MAX);
+        let overflowing = view_effective_deadline(&ctx, &host);
+
+        // assert
+        assert_eq!(
+            with_grace,
+            Ok(EffectiveDeadline {
+                end_time: Timestamp::from_timestamp_millis(10000),
+                grace_period: Duration::from_millis(250),
+                deadline: Some(Timestamp::from_timestamp_millis(10250)),
+            }),
+            "The deadline should include the grace period"
+        );
+        assert_eq!(overflowing.map(|view| view.deadline), Ok(None), "An overflowing deadline should be None");
+    }
+
+    #[test]
+    fn test_cancel_deferred() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&DonateParams {
+            activate_at: Some(Timestamp::from_timestamp_millis(5000)),
+            ..donate_params("CM")
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init()).is_ok());
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(200), &mut TestLogger::init()).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(300));
+
+        // act
+        let first = to_bytes(&0u64);
+        ctx.set_parameter(&first);
+        let cancelled = cancel_deferred(&ctx, &mut host);
+        let cancelled_again = cancel_deferred(&ctx, &mut host);
+        let second = to_bytes(&1u64);
+        ctx.set_parameter(&second);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(5000));
+        assert!(activate_pending(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+        let activated = cancel_deferred(&ctx, &mut host);
+
+        // assert
+        assert!(cancelled.is_ok(), "Failed to cancel the deferred donation.");
+        assert_eq!(cancelled_again, Err(Error::UnknownPending), "Cancelled donations are no longer held");
+        assert_eq!(host.get_transfers(), [(ACC, Amount::from_micro_ccd(100))], "The held CCD should be returned");
+        assert_eq!(activated, Err(Error::AlreadyActivated), "Activated donations cannot be cancelled");
+        assert_eq!(host.state().pending_total, Amount::zero(), "Nothing should be held any more");
+        assert_eq!(host.state().total_donated, Amount::from_micro_ccd(200), "Only the other donation should count");
+    }
+
+    #[test]
+    fn test_view_size_report() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&DonateParams {
+            activate_at: Some(Timestamp::from_timestamp_millis(5000)),
+            ..donate_params("CM")
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init()).is_ok());
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        assert!(donate_as(&mut host, AccountAddress([1u8; 32]), "GE", Amount::from_micro_ccd(100)).is_ok());
+
+        // act
+        let report = view_size_report(&ctx, &host);
+
+        // assert
+        assert_eq!(
+            report,
+            Ok(SizeReport {
+                donors: 2,
+                locations: 4,
+                pending: 1,
+                tags: 0,
+                recent_donations: 2,
+                checkpoints: 0,
+            }),
+            "The report should match the populated state"
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_locations() {
+        for case_insensitive in [false, true] {
+            // arrange
+            let mut state_builder = TestStateBuilder::new();
+            let mut state = test_state(&mut state_builder);
+            state.case_insensitive_locations = case_insensitive;
+            let mut host = TestHost::new(state, state_builder);
+
+            // act
+            let result = donate_as(&mut host, ACC, "cm", Amount::from_micro_ccd(100));
+
+            // assert
+            let cm_total = host.state().location_totals.get(&"CM".to_string()).map(|total| *total);
+            if case_insensitive {
+                assert!(result.is_ok(), "Differently cased locations should be accepted");
+                assert_eq!(cm_total, Some(Amount::from_micro_ccd(100)), "The canonical location should be credited");
+                assert_eq!(
+                    host.state().recent_donations[0].0.location,
+                    "CM".to_string(),
+                    "The canonical casing should be stored"
+                );
+            } else {
+                assert_eq!(result, Err(Error::InvalidDonationLocation), "Casing should matter by default");
+                assert_eq!(cm_total, Some(Amount::zero()), "Nothing should be credited");
+            }
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_location_views() {
+        for case_insensitive in [false, true] {
+            // arrange
+            let mut state_builder = TestStateBuilder::new();
+            let mut state = test_state(&mut state_builder);
+            state.case_insensitive_locations = case_insensitive;
+            let mut host = TestHost::new(state, state_builder);
+            assert!(donate_as(&mut host, ACC, "CM", Amount::from_micro_ccd(100)).is_ok());
+            let mut ctx = TestReceiveContext::empty();
+            let parameter = to_bytes(&"cm".to_string());
+            ctx.set_parameter(&parameter);
+
+            // act
+            let exists = view_location_exists(&ctx, &host);
+            let view = view_location(&ctx, &host);
+
+            // assert
+            if case_insensitive {
+                assert_eq!(exists, Ok(true), "Differently cased locations should exist");
+                assert_eq!(
+                    view.map(|view| (view.location, view.total)),
+                    Ok(("CM".to_string(), Amount::from_micro_ccd(100))),
+                    "The canonical location should be returned"
+                );
+            } else {
+                assert_eq!(exists, Ok(false), "Casing should matter by default");
+                assert_eq!(view.err(), Some(Error::InvalidDonationLocation), "Casing should matter by default");
+            }
+        }
+    }
+
+    #[test]
+    fn test_finalize_forever() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(100));
+        let while_open = finalize_forever(&ctx, &mut host);
+        assert!(close(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+
+        // act
+        let finalized = finalize_forever(&ctx, &mut host);
+        let donated = donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100));
+        let opened = open(&ctx, &mut host, &mut TestLogger::init());
+        let locked = lock_goal(&ctx, &mut host);
+        let checkpoint = snapshot_checkpoint(&ctx, &mut host);
+        let activated = activate_pending(&ctx, &mut host, &mut TestLogger::init());
+        let finalized_again = finalize_forever(&ctx, &mut host);
+        let stats = view_stats(&ctx, &host);
+
+        // assert
+        assert_eq!(while_open, Err(Error::DonationClosed), "Only closed campaigns can be finalized");
+        assert!(finalized.is_ok(), "Failed to finalize.");
+        assert_eq!(donated, Err(Error::Finalized), "Donating should be blocked");
+        assert_eq!(opened, Err(Error::Finalized.into()), "Opening should be blocked");
+        assert_eq!(locked, Err(Error::Finalized), "Locking the goal should be blocked");
+        assert_eq!(checkpoint, Err(Error::Finalized), "Checkpoints should be blocked");
+        assert_eq!(activated, Err(Error::Finalized), "Activating should be blocked");
+        assert_eq!(finalized_again, Err(Error::Finalized), "Finalizing again should be blocked");
+        assert_eq!(
+            stats.map(|stats| stats.total_donated),
+            Ok(Amount::from_micro_ccd(100)),
+            "Views should keep working"
+        );
+    }
+
+    #[test]
+    fn test_finalize_forever_with_held_donations() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&DonateParams {
+            activate_at: Some(Timestamp::from_timestamp_millis(5000)),
+            ..donate_params("GE")
+        });
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut TestLogger::init()).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(100));
+        assert!(close(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+
+        // act
+        let finalized = finalize_forever(&ctx, &mut host);
+        let id = to_bytes(&0u64);
+        ctx.set_parameter(&id);
+        let cancelled = cancel_deferred(&ctx, &mut host);
+
+        // assert
+        assert_eq!(finalized, Err(Error::DonationsHeld), "Held donations should block finalizing");
+        assert!(cancelled.is_ok(), "The held donation should stay cancellable");
+        assert_eq!(host.state().pending_total, Amount::zero(), "Nothing should be held any more");
+    }
+
+    #[test]
+    fn test_donation_events() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+        ctx.set_metadata_slot_time(Timestamp::from_timestamp_millis(0));
+        let parameter = to_bytes(&donate_params("IT"));
+        ctx.set_parameter(&parameter);
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        host.set_self_balance(Amount::from_micro_ccd(100));
+        let mut logger = TestLogger::init();
+
+        // act
+        assert!(donate(&ctx, &mut host, Amount::from_micro_ccd(100), &mut logger).is_ok());
+        assert!(close(&ctx, &mut host, &mut logger).is_ok());
+        assert!(open(&ctx, &mut host, &mut logger).is_ok());
+
+        // assert
+        let events: Vec<DonationEvent> =
+            logger.logs.iter().map(|log| from_bytes(log).expect("Event should decode")).collect();
+        assert_eq!(
+            events,
+            vec![
+                DonationEvent::NewDonation {
+                    donor: ACC,
+                    location: "IT".to_string(),
+                    amount: Amount::from_micro_ccd(100),
+                },
+                DonationEvent::Closed,
+                DonationEvent::Opened,
+            ],
+            "Donating, closing and opening should be logged"
+        );
+    }
+
+    #[test]
+    fn test_extend() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        let later = to_bytes(&Timestamp::from_timestamp_millis(20000));
+        let earlier = to_bytes(&Timestamp::from_timestamp_millis(15000));
+
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+
+        // act
+        ctx.set_sender(Address::Account(AccountAddress([1u8; 32])));
+        ctx.set_parameter(&later);
+        let non_owner = extend(&ctx, &mut host);
+        ctx.set_sender(Address::Account(ACC));
+        let extended = extend(&ctx, &mut host);
+        let equal = extend(&ctx, &mut host);
+        ctx.set_parameter(&earlier);
+        let past = extend(&ctx, &mut host);
+
+        // assert
+        assert_eq!(non_owner, Err(Error::Unauthorized), "Only the owner should extend");
+        assert!(extended.is_ok(), "Failed to extend.");
+        assert_eq!(equal, Err(Error::InvalidEndTime), "The same end time should be rejected");
+        assert_eq!(past, Err(Error::InvalidEndTime), "An earlier end time should be rejected");
+        assert_eq!(host.state().end_time, Timestamp::from_timestamp_millis(20000), "End time should be extended");
+    }
+
+    #[test]
+    fn test_view_locations() {
+        // arrange
+        let ctx = TestReceiveContext::empty();
+        let mut state_builder = TestStateBuilder::new();
+        let state = test_state(&mut state_builder);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(50)).is_ok());
+        assert!(donate_as(&mut host, AccountAddress([1u8; 32]), "GE", Amount::from_micro_ccd(25)).is_ok());
+        assert!(donate_as(&mut host, ACC, "IT", Amount::from_micro_ccd(200)).is_ok());
+
+        // act
+        let locations = view_locations(&ctx, &host).unwrap();
+
+        // assert
+        let breakdown: Vec<(DonationLocation, Amount, u32)> = locations
+            .into_iter()
+            .map(|location| (location.location, location.total, location.donors))
+            .collect();
+        assert_eq!(
+            breakdown,
+            vec![
+                ("GE".to_string(), Amount::from_micro_ccd(175), 2),
+                ("CM".to_string(), Amount::zero(), 0),
+                ("IT".to_string(), Amount::from_micro_ccd(200), 1),
+                ("FR".to_string(), Amount::zero(), 0),
+            ],
+            "Every location should appear in init order, untouched ones with zero"
+        );
+    }
+
+    #[test]
+    fn test_refund() {
+        // arrange
+        let donor = AccountAddress([1u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(1000);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, donor, "GE", Amount::from_micro_ccd(100)).is_ok());
+        assert!(donate_as(&mut host, donor, "CM", Amount::from_micro_ccd(50)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(150));
+
+        // act
+        let closed = close(&ctx, &mut host, &mut TestLogger::init());
+        let swept = host.get_transfers().len();
+        ctx.set_sender(Address::Account(donor));
+        ctx.set_invoker(donor);
+        let refunded = refund(&ctx, &mut host);
+        let double_refund = refund(&ctx, &mut host);
+
+        // assert
+        assert!(closed.is_ok(), "Failed to close donation.");
+        assert_eq!(swept, 0, "Nothing should go to the owner below the goal");
+        assert_eq!(host.state().state_of_donation, StateOfDonation::Refunding, "Donation should be refunding");
+        assert!(refunded.is_ok(), "Failed to refund.");
+        assert_eq!(double_refund, Err(Error::NothingToRefund), "Refunding twice should be rejected");
+        assert_eq!(host.get_transfers(), [(donor, Amount::from_micro_ccd(150))], "wrong transfers.");
+    }
+
+    #[test]
+    fn test_withdraw_below_goal_then_refund() {
+        // arrange
+        let donor = AccountAddress([1u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let withdrawal = to_bytes(&WithdrawParams {
+            amount: Amount::from_micro_ccd(100),
+            splits: vec![(ACC, Amount::from_micro_ccd(100))],
+        });
+        ctx.set_parameter(&withdrawal);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(1000);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, donor, "GE", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(100));
+
+        // act
+        let withdrawn = withdraw(&ctx, &mut host);
+        let closed = close(&ctx, &mut host, &mut TestLogger::init());
+        ctx.set_sender(Address::Account(donor));
+        ctx.set_invoker(donor);
+        let refunded = refund(&ctx, &mut host);
+
+        // assert
+        assert_eq!(withdrawn, Err(Error::GoalNotReached), "Withdrawing below the goal should be rejected");
+        assert!(closed.is_ok(), "Failed to close donation.");
+        assert!(refunded.is_ok(), "Failed to refund.");
+        assert_eq!(host.get_transfers(), [(donor, Amount::from_micro_ccd(100))], "wrong transfers.");
+    }
+
+    #[test]
+    fn test_set_goal_after_withdraw() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let withdrawal = to_bytes(&WithdrawParams {
+            amount: Amount::from_micro_ccd(100),
+            splits: vec![(ACC, Amount::from_micro_ccd(100))],
+        });
+        let higher_goal = to_bytes(&Amount::from_micro_ccd(1000));
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(100);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(100));
+
+        // act
+        ctx.set_parameter(&withdrawal);
+        assert!(withdraw(&ctx, &mut host).is_ok());
+        ctx.set_parameter(&higher_goal);
+        let raised = set_goal(&ctx, &mut host);
+        let closed = close(&ctx, &mut host, &mut TestLogger::init());
+
+        // assert
+        assert_eq!(raised, Err(Error::FundsPaidOut), "The goal should not move past what was paid out");
+        assert!(closed.is_ok(), "Failed to close donation.");
+        assert_eq!(host.state().state_of_donation, StateOfDonation::Closed, "Donation should not be refunding");
+    }
+
+    #[test]
+    fn test_refunding_seed_and_finalize() {
+        // arrange
+        let donor = AccountAddress([1u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        let withdrawal = to_bytes(&WithdrawParams {
+            amount: Amount::from_micro_ccd(50),
+            splits: vec![(ACC, Amount::from_micro_ccd(50))],
+        });
+        ctx.set_parameter(&withdrawal);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(1000);
+        state.seed_balance = Amount::from_micro_ccd(50);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, donor, "GE", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(150));
+        assert!(close(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+
+        // act
+        let available = host.state().available_balance(host.self_balance());
+        let withdrawn = withdraw(&ctx, &mut host);
+        let finalized_early = finalize_forever(&ctx, &mut host);
+        let mut donor_ctx = TestReceiveContext::empty();
+        donor_ctx.set_sender(Address::Account(donor));
+        donor_ctx.set_invoker(donor);
+        let refunded = refund(&donor_ctx, &mut host);
+        let finalized = finalize_forever(&ctx, &mut host);
+
+        // assert
+        assert_eq!(available, Amount::from_micro_ccd(50), "Only the seed should be available");
+        assert!(withdrawn.is_ok(), "Failed to withdraw the seed.");
+        assert_eq!(finalized_early, Err(Error::RefundsOutstanding), "Finalizing should wait for the refunds");
+        assert!(refunded.is_ok(), "Failed to refund.");
+        assert!(finalized.is_ok(), "Failed to finalize.");
+        assert_eq!(
+            host.get_transfers(),
+            [(ACC, Amount::from_micro_ccd(50)), (donor, Amount::from_micro_ccd(100))],
             "wrong transfers."
         );
+    }
 
-        // open
-        let openResult = open(&ctx, &mut host);
-        assert!(result.is_ok(), "Failed to open donation.");
-        assert_eq!(host.state().state_of_donation, StateOfDonation::Open, "State of donation should be open.");
+    #[test]
+    fn test_refund_fee() {
+        // arrange
+        let donor = AccountAddress([1u8; 32]);
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(1000);
+        state.refund_fee_bps = 1000;
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, donor, "GE", Amount::from_micro_ccd(150)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(150));
+        assert!(close(&ctx, &mut host, &mut TestLogger::init()).is_ok());
+
+        // act
+        ctx.set_sender(Address::Account(donor));
+        ctx.set_invoker(donor);
+        let refunded = refund(&ctx, &mut host);
+        let invariants = view_check_invariants(&ctx, &host).unwrap();
+
+        // assert
+        assert!(refunded.is_ok(), "Failed to refund.");
+        assert_eq!(host.get_transfers(), [(donor, Amount::from_micro_ccd(135))], "The fee should be kept");
+        assert_eq!(
+            host.state().available_balance(host.self_balance()),
+            Amount::from_micro_ccd(15),
+            "The fee should be available to the owner"
+        );
+        assert!(invariants.consistent, "The fee should reconcile with the balance");
+    }
+
+    #[test]
+    fn test_init_refund_fee_too_high() {
+        // arrange
+        let mut init_ctx = TestInitContext::empty();
+        init_ctx.set_init_origin(ACC);
+        let parameter = to_bytes(&InitParameter {
+            refund_fee_bps: 10001,
+            ..init_parameter()
+        });
+        init_ctx.set_parameter(&parameter);
+        let mut state_builder = TestStateBuilder::new();
+
+        // act
+        let result = init(&init_ctx, &mut state_builder, Amount::zero());
+
+        // assert
+        assert_eq!(result.err(), Some(Error::InvalidBasisPoints.into()), "The fee cannot exceed the refund");
+    }
+
+    #[test]
+    fn test_close_goal_met_pays_owner() {
+        // arrange
+        let mut ctx = TestReceiveContext::empty();
+        ctx.set_owner(ACC);
+        ctx.set_sender(Address::Account(ACC));
+        ctx.set_invoker(ACC);
+
+        let mut state_builder = TestStateBuilder::new();
+        let mut state = test_state(&mut state_builder);
+        state.goal = Amount::from_micro_ccd(100);
+        let mut host = TestHost::new(state, state_builder);
+        assert!(donate_as(&mut host, ACC, "GE", Amount::from_micro_ccd(100)).is_ok());
+        host.set_self_balance(Amount::from_micro_ccd(100));
+
+        // act
+        let closed = close(&ctx, &mut host, &mut TestLogger::init());
+        let refunded = refund(&ctx, &mut host);
+
+        // assert
+        assert!(closed.is_ok(), "Failed to close donation.");
+        assert_eq!(host.state().state_of_donation, StateOfDonation::Closed, "State of donation should be closed.");
+        assert_eq!(host.get_transfers(), [(ACC, Amount::from_micro_ccd(100))], "wrong transfers.");
+        assert_eq!(refunded, Err(Error::NotRefunding), "Refunds should only be possible below the goal");
     }
-}
\ No newline at end of file
+}